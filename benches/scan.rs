@@ -0,0 +1,26 @@
+use std::time::Instant;
+
+use lox::Scanner;
+
+// a statement mixing every kind of token the scanner has a fast path for
+const LINE: &str = "var total = count * 2.5 + \"label\"; // running sum\n";
+const SIZE: usize = 1 << 20;
+const RUNS: u32 = 10;
+
+fn main() {
+    let src = LINE.repeat(SIZE / LINE.len() + 1);
+    let mut tokens = 0;
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        tokens = Scanner::new(&src).scan().expect("source scans").len();
+    }
+    let per_run = start.elapsed() / RUNS;
+    let mb_per_sec = src.len() as f64 / per_run.as_secs_f64() / 1e6;
+    println!(
+        "scanned {} bytes into {} tokens in {:?} ({:.1} MB/s)",
+        src.len(),
+        tokens,
+        per_run,
+        mb_per_sec
+    );
+}
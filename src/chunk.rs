@@ -0,0 +1,155 @@
+use crate::{Span, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    // followed by a one-byte index into the constant pool
+    Constant,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Negate,
+    Return,
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<OpCode, u8> {
+        let op = match byte {
+            0 => OpCode::Constant,
+            1 => OpCode::Add,
+            2 => OpCode::Subtract,
+            3 => OpCode::Multiply,
+            4 => OpCode::Divide,
+            5 => OpCode::Modulo,
+            6 => OpCode::Negate,
+            7 => OpCode::Return,
+            _ => return Err(byte),
+        };
+        Ok(op)
+    }
+}
+
+// a compiled sequence of instructions, with the source span of every byte
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    pub spans: Vec<Span>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk::default()
+    }
+
+    pub fn write_byte(&mut self, byte: u8, span: Span) {
+        self.code.push(byte);
+        self.spans.push(span);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
+        self.write_byte(op as u8, span);
+    }
+
+    // emits a Constant instruction loading value; None if the pool is already full
+    pub fn write_constant(&mut self, value: Value, span: Span) -> Option<u8> {
+        let index = u8::try_from(self.constants.len()).ok()?;
+        self.constants.push(value);
+        self.write_op(OpCode::Constant, span);
+        self.write_byte(index, span);
+        Some(index)
+    }
+
+    // the source the byte at offset was compiled from
+    pub fn span_at(&self, offset: usize) -> Span {
+        self.spans[offset]
+    }
+}
+
+// renders every instruction in the chunk, in the style of clox
+pub fn disassemble(chunk: &Chunk, name: &str) -> String {
+    let mut out = format!("== {} ==\n", name);
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let (text, next) = disassemble_instruction(chunk, offset);
+        out.push_str(&text);
+        out.push('\n');
+        offset = next;
+    }
+    out
+}
+
+// renders the instruction at offset, returning it and the offset of the next one
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
+    let mut out = format!("{:04} ", offset);
+    let line = chunk.span_at(offset).line;
+    if offset > 0 && line == chunk.span_at(offset - 1).line {
+        out.push_str("   | ");
+    } else {
+        out.push_str(&format!("{:4} ", line));
+    }
+    let op = match OpCode::try_from(chunk.code[offset]) {
+        Ok(op) => op,
+        Err(byte) => {
+            out.push_str(&format!("Unknown opcode {}", byte));
+            return (out, offset + 1);
+        }
+    };
+    let name = match op {
+        OpCode::Constant => "OP_CONSTANT",
+        OpCode::Add => "OP_ADD",
+        OpCode::Subtract => "OP_SUBTRACT",
+        OpCode::Multiply => "OP_MULTIPLY",
+        OpCode::Divide => "OP_DIVIDE",
+        OpCode::Modulo => "OP_MODULO",
+        OpCode::Negate => "OP_NEGATE",
+        OpCode::Return => "OP_RETURN",
+    };
+    match op {
+        OpCode::Constant => {
+            let index = chunk.code[offset + 1];
+            out.push_str(&format!(
+                "{:<16} {:4} '{}'",
+                name, index, chunk.constants[index as usize]
+            ));
+            (out, offset + 2)
+        }
+        _ => {
+            out.push_str(name);
+            (out, offset + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(line: usize) -> Span {
+        Span {
+            line,
+            ..Span::default()
+        }
+    }
+
+    #[test]
+    fn disassembles_each_instruction() {
+        let mut chunk = Chunk::new();
+        chunk.write_constant(Value::Number(1.2), line(1));
+        chunk.write_op(OpCode::Negate, line(1));
+        chunk.write_op(OpCode::Return, line(2));
+        chunk.write_byte(200, line(2));
+        assert_eq!(
+            disassemble(&chunk, "test"),
+            "== test ==\n\
+             0000    1 OP_CONSTANT         0 '1.2'\n\
+             0002    | OP_NEGATE\n\
+             0003    2 OP_RETURN\n\
+             0004    | Unknown opcode 200\n"
+        );
+    }
+}
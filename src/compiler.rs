@@ -0,0 +1,141 @@
+use std::fmt::Display;
+
+use crate::{BinaryOp, Chunk, Expr, Literal, OpCode, Span, UnaryOp, Value};
+
+// compiles a single expression into a chunk that evaluates it and returns the result
+pub fn compile_to_chunk(expr: &Expr) -> Result<Chunk, CompileError> {
+    let mut compiler = Compiler {
+        chunk: Chunk::new(),
+    };
+    compiler.expr(expr)?;
+    compiler.chunk.write_op(OpCode::Return, expr.span());
+    Ok(compiler.chunk)
+}
+
+struct Compiler {
+    chunk: Chunk,
+}
+
+impl Compiler {
+    fn expr(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match expr {
+            Expr::Literal { value, span } => {
+                let value = match value {
+                    Literal::Number(n) => Value::Number(*n),
+                    Literal::Int(_) => {
+                        return Err(error(*span, "Integers can't be compiled to bytecode yet."))
+                    }
+                    Literal::Str(s) => Value::Str(s.clone()),
+                    Literal::Bool(b) => Value::Bool(*b),
+                    Literal::Nil => Value::Nil,
+                };
+                if self.chunk.write_constant(value, *span).is_none() {
+                    return Err(error(*span, "Too many constants in one chunk."));
+                }
+            }
+            Expr::Grouping { expr, .. } => self.expr(expr)?,
+            Expr::Unary { op, span, right } => {
+                self.expr(right)?;
+                match op {
+                    UnaryOp::Neg => self.chunk.write_op(OpCode::Negate, *span),
+                    UnaryOp::Not | UnaryOp::BitNot | UnaryOp::ToStr => {
+                        return Err(unsupported(*span, &op.to_string()))
+                    }
+                }
+            }
+            Expr::Binary {
+                left,
+                op,
+                span,
+                right,
+            } => {
+                self.expr(left)?;
+                self.expr(right)?;
+                let op = match op {
+                    BinaryOp::Add => OpCode::Add,
+                    BinaryOp::Sub => OpCode::Subtract,
+                    BinaryOp::Mul => OpCode::Multiply,
+                    BinaryOp::Div => OpCode::Divide,
+                    BinaryOp::Rem => OpCode::Modulo,
+                    op => return Err(unsupported(*span, &op.to_string())),
+                };
+                self.chunk.write_op(op, *span);
+            }
+            _ => {
+                return Err(error(
+                    expr.span(),
+                    "Expression can't be compiled to bytecode yet.",
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn unsupported(span: Span, op: &str) -> CompileError {
+    error(
+        span,
+        &format!("Operator '{}' can't be compiled to bytecode yet.", op),
+    )
+}
+
+fn error(span: Span, message: &str) -> CompileError {
+    CompileError {
+        span,
+        message: message.to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub struct CompileError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Error: {}", self.span.line, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, Scanner};
+
+    fn compile(src: &str) -> Chunk {
+        let expr = Parser::new(Scanner::new(src).scan().unwrap())
+            .parse()
+            .unwrap();
+        compile_to_chunk(&expr).unwrap()
+    }
+
+    #[test]
+    fn compiles_an_addition() {
+        let chunk = compile("1 + 2");
+        assert_eq!(
+            chunk.code,
+            [
+                OpCode::Constant as u8,
+                0,
+                OpCode::Constant as u8,
+                1,
+                OpCode::Add as u8,
+                OpCode::Return as u8
+            ]
+        );
+        assert_eq!(chunk.constants, [Value::Number(1.0), Value::Number(2.0)]);
+    }
+
+    #[test]
+    fn unsupported_expressions_are_errors() {
+        let expr = Parser::new(Scanner::new("1 < 2").scan().unwrap())
+            .parse()
+            .unwrap();
+        let error = compile_to_chunk(&expr).unwrap_err();
+        assert_eq!(
+            error.message,
+            "Operator '<' can't be compiled to bytecode yet."
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::ops::Range;
+
+use crate::{LineIndex, Span};
+
+const TAB_WIDTH: usize = 4;
+
+// message followed by the line span starts on, with the span underlined like rustc does:
+//
+//   [line 1:7] Error: Unexpected character '@'.
+//     |
+//   1 | print @;
+//     |       ^
+pub fn render_diagnostic(src: &str, span: Span, message: &str) -> String {
+    render_diagnostic_with_tab_width(src, span, message, TAB_WIDTH)
+}
+
+// like render_diagnostic, with tabs expanded to stops tab_width columns apart
+pub fn render_diagnostic_with_tab_width(
+    src: &str,
+    span: Span,
+    message: &str,
+    tab_width: usize,
+) -> String {
+    let start = span.start.min(src.len());
+    let index = LineIndex::new(src);
+    let (line, _) = index.offset_to_line_col(start);
+    let Range {
+        start: line_start,
+        end: line_end,
+    } = index.line_range(line);
+    let line_start = line_start.min(start);
+    // a span running past the line is underlined to its end; an empty one still gets a caret
+    let end = span.end.clamp(start, line_end);
+    let tab_width = tab_width.max(1);
+    let pad = expand_tabs(&src[line_start..start], tab_width)
+        .chars()
+        .count();
+    let width = expand_tabs(&src[line_start..end], tab_width)
+        .chars()
+        .count()
+        - pad;
+
+    let gutter = " ".repeat(span.line.to_string().len());
+    format!(
+        "{}\n{} |\n{} | {}\n{} | {}{}",
+        message,
+        gutter,
+        span.line,
+        expand_tabs(&src[line_start..line_end], tab_width),
+        gutter,
+        " ".repeat(pad),
+        "^".repeat(width.max(1)),
+    )
+}
+
+// replaces each tab with spaces up to the next tab stop
+fn expand_tabs(text: &str, tab_width: usize) -> String {
+    let mut expanded = String::new();
+    let mut col = 0;
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = tab_width - col % tab_width;
+            expanded.push_str(&" ".repeat(spaces));
+            col += spaces;
+        } else {
+            expanded.push(c);
+            col += 1;
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scanner;
+
+    #[test]
+    fn scan_error_is_underlined_at_its_column() {
+        let src = "var x = 1;\nprint x @ 2;";
+        let errors = Scanner::new(src).scan().unwrap_err();
+        let error = &errors[0];
+        assert_eq!((error.span.line, error.span.col), (2, 9));
+        assert_eq!(
+            render_diagnostic(src, error.span, &error.to_string()),
+            "[line 2:9] Error: Unexpected character '@'.\n  |\n2 | print x @ 2;\n  |         ^"
+        );
+    }
+
+    #[test]
+    fn span_running_past_the_line_stops_at_its_end() {
+        let src = "print \"abc\nprint 1;";
+        let span = Span {
+            start: 6,
+            end: src.len(),
+            line: 1,
+            col: 7,
+        };
+        assert_eq!(
+            render_diagnostic(src, span, "Unterminated string."),
+            "Unterminated string.\n  |\n1 | print \"abc\n  |       ^^^^"
+        );
+    }
+
+    #[test]
+    fn empty_span_at_the_end_of_input_still_gets_a_caret() {
+        let src = "print 1";
+        let span = Span {
+            start: 7,
+            end: 7,
+            line: 1,
+            col: 8,
+        };
+        assert_eq!(
+            render_diagnostic(src, span, "Expect ';' after value."),
+            "Expect ';' after value.\n  |\n1 | print 1\n  |        ^"
+        );
+    }
+
+    #[test]
+    fn tabs_are_expanded_so_the_caret_lines_up() {
+        let src = "\tprint @;";
+        let span = Span {
+            start: 7,
+            end: 8,
+            line: 1,
+            col: 8,
+        };
+        assert_eq!(
+            render_diagnostic_with_tab_width(src, span, "Error.", 8),
+            "Error.\n  |\n1 |         print @;\n  |               ^"
+        );
+    }
+}
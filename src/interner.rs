@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::{Token, TokenType};
+
+// a cheap, copyable stand-in for an interned string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+// maps each distinct string to one Symbol, so comparing names is comparing integers
+#[derive(Debug, Default)]
+pub struct Interner {
+    symbols: HashMap<String, Symbol>,
+    strings: Vec<String>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(s) {
+            return symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(s.to_string());
+        self.symbols.insert(s.to_string(), symbol);
+        symbol
+    }
+
+    // interns an identifier's name or a string literal's value; other tokens have no symbol
+    pub fn intern_token(&mut self, token: &Token) -> Option<Symbol> {
+        match &token.typ {
+            TokenType::Ident => Some(self.intern(token.lexeme)),
+            TokenType::Str(s) => Some(self.intern(s)),
+            _ => None,
+        }
+    }
+
+    // the post-pass over a scan: the symbol of each token in tokens, in order, so symbols[i]
+    // belongs to tokens[i]
+    pub fn intern_tokens(&mut self, tokens: &[Token]) -> Vec<Option<Symbol>> {
+        tokens
+            .iter()
+            .map(|token| self.intern_token(token))
+            .collect()
+    }
+
+    // symbols only come from intern, so this can't be out of range for its own interner
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scanner;
+
+    #[test]
+    fn same_string_interns_to_the_same_symbol() {
+        let mut interner = Interner::new();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        assert_eq!(interner.intern("a"), a);
+        assert_ne!(a, b);
+        assert_eq!(interner.resolve(b), "b");
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn identifiers_and_strings_of_a_scan_are_interned() {
+        let tokens = Scanner::new("x + y + x + \"x\"").scan().unwrap();
+        let mut interner = Interner::new();
+        let symbols = interner.intern_tokens(&tokens);
+        assert_eq!(symbols.len(), tokens.len());
+        let x = symbols[0].unwrap();
+        assert_ne!(symbols[2], Some(x));
+        assert_eq!(symbols[4], Some(x));
+        // a string literal interns its value, so it shares the identifier's symbol
+        assert_eq!(symbols[6], Some(x));
+        assert_eq!(symbols[1], None);
+        assert_eq!(interner.resolve(x), "x");
+        assert_eq!(interner.len(), 2);
+    }
+}
@@ -0,0 +1,2287 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Display,
+    io::{self, BufRead, Write},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    scanner::escape_str, BinaryOp, Expr, ExprId, FunctionDecl, Literal, LogicalOp, Scanner, Span,
+    Stmt, UnaryOp, UpdateOp,
+};
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(f64),
+    // from integer literals when ScannerConfig::integers is on
+    Int(i64),
+    Str(String),
+    Bool(bool),
+    Nil,
+    Function(Rc<Function>),
+    Native(Rc<Native>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+}
+
+pub struct Function {
+    decl: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        &self.decl.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.decl.params.len()
+    }
+
+    // a copy of the method whose closure has `this` bound to instance
+    fn bind(&self, instance: Rc<RefCell<Instance>>) -> Function {
+        let mut env = Environment::with_enclosing(Rc::clone(&self.closure));
+        env.define("this", Value::Instance(instance));
+        Function {
+            decl: Rc::clone(&self.decl),
+            closure: Rc::new(RefCell::new(env)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            "" => write!(f, "<fn>"),
+            name => write!(f, "<fn {}>", name),
+        }
+    }
+}
+
+// the closure may contain the function itself, so don't recurse into it
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    fn find_method(&self, name: &str) -> Option<&Rc<Function>> {
+        match (self.methods.get(name), &self.superclass) {
+            (Some(method), _) => Some(method),
+            (None, Some(superclass)) => superclass.find_method(name),
+            (None, None) => None,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+}
+
+// only strings and numbers can be map keys, since they're the values compared by content
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Str(String),
+    // the bits of the f64, with -0 folded into 0
+    Number(u64),
+    // only for ints that an f64 can't represent exactly, so 1 and 1.0 are the same key
+    Int(i64),
+}
+
+impl MapKey {
+    pub fn new(value: &Value) -> Option<MapKey> {
+        match value {
+            Value::Str(s) => Some(MapKey::Str(s.clone())),
+            Value::Number(n) if n.is_nan() => None,
+            Value::Number(n) => Some(MapKey::Number((n + 0.0).to_bits())),
+            Value::Int(n) if *n as f64 as i64 == *n && *n != i64::MAX => {
+                Some(MapKey::Number((*n as f64).to_bits()))
+            }
+            Value::Int(n) => Some(MapKey::Int(*n)),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Str(s) => Value::Str(s.clone()),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::Int(n) => Value::Int(*n),
+        }
+    }
+}
+
+// a hash map that remembers insertion order, so printing one is deterministic
+#[derive(Debug, Default)]
+pub struct Map {
+    indices: HashMap<MapKey, usize>,
+    entries: Vec<(MapKey, Value)>,
+}
+
+impl Map {
+    pub fn get(&self, key: &MapKey) -> Option<&Value> {
+        self.indices.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn insert(&mut self, key: MapKey, value: Value) {
+        match self.indices.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&MapKey, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    fields: HashMap<String, Value>,
+}
+
+impl Value {
+    // only nil and false are falsy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    // the name returned by the type() native; ints are numbers too
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) | Value::Int(_) => "number",
+            Value::Str(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+
+    // ints are promoted to floats when mixed with them
+    fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Number(n) => Some(n),
+            Value::Int(n) => Some(n as f64),
+            _ => None,
+        }
+    }
+}
+
+// natives get the interpreter so they can call back into Lox functions
+pub type NativeFn = dyn Fn(&mut Interpreter, &[Value]) -> Result<Value, String>;
+
+// a builtin implemented in Rust; errors are reported at the call site
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    pub func: Box<NativeFn>,
+}
+
+impl std::fmt::Debug for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Int(a), Value::Number(b)) | (Value::Number(b), Value::Int(a)) => {
+                *a as f64 == *b
+            }
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Nil => write!(f, "nil"),
+            Value::Function(fun) => write!(f, "{}", fun),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+            Value::List(list) => {
+                write!(f, "[")?;
+                for (i, value) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key.to_value(), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// shows a value as source that evaluates to it where possible, e.g. the string a<newline>b as
+// "a\nb", unlike Display, which is what print writes
+pub struct Repr<'a>(&'a Value);
+
+impl Value {
+    pub fn repr(&self) -> Repr<'_> {
+        Repr(self)
+    }
+}
+
+impl Display for Repr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Value::Str(s) => write!(f, "\"{}\"", escape_str(s)),
+            Value::List(list) => {
+                write!(f, "[")?;
+                for (i, value) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value.repr())?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key.to_value().repr(), value.repr())?;
+                }
+                write!(f, "}}")
+            }
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str, span: Span) -> Result<Value, RuntimeError> {
+        match (self.values.get(name), &self.enclosing) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, Some(enclosing)) => enclosing.borrow().get(name, span),
+            (None, None) => Err(undefined_variable(name, span)),
+        }
+    }
+
+    // overwrites the nearest existing binding of name
+    pub fn assign(&mut self, name: &str, value: Value, span: Span) -> Result<(), RuntimeError> {
+        match (self.values.get_mut(name), &self.enclosing) {
+            (Some(slot), _) => {
+                *slot = value;
+                Ok(())
+            }
+            (None, Some(enclosing)) => enclosing.borrow_mut().assign(name, value, span),
+            (None, None) => Err(undefined_variable(name, span)),
+        }
+    }
+}
+
+impl Environment {
+    // looks name up exactly distance scopes out, as computed by the resolver
+    pub fn get_at(&self, distance: usize, name: &str, span: Span) -> Result<Value, RuntimeError> {
+        match (distance, &self.enclosing) {
+            (0, _) => self
+                .values
+                .get(name)
+                .cloned()
+                .ok_or_else(|| undefined_variable(name, span)),
+            (_, Some(enclosing)) => enclosing.borrow().get_at(distance - 1, name, span),
+            (_, None) => Err(undefined_variable(name, span)),
+        }
+    }
+
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &str,
+        value: Value,
+        span: Span,
+    ) -> Result<(), RuntimeError> {
+        match (distance, &self.enclosing) {
+            (0, _) => match self.values.get_mut(name) {
+                Some(slot) => {
+                    *slot = value;
+                    Ok(())
+                }
+                None => Err(undefined_variable(name, span)),
+            },
+            (_, Some(enclosing)) => {
+                enclosing
+                    .borrow_mut()
+                    .assign_at(distance - 1, name, value, span)
+            }
+            (_, None) => Err(undefined_variable(name, span)),
+        }
+    }
+}
+
+// a copy of one scope's bindings, taken by Environment::snapshot
+#[derive(Debug)]
+pub struct EnvSnapshot {
+    values: HashMap<String, Value>,
+}
+
+impl Environment {
+    // copies this scope's bindings, but not enclosing ones. lists, maps and instances are deep
+    // cloned so that mutating them is rolled back too, which costs time and memory in
+    // proportion to everything reachable from the bindings. functions and classes are shared
+    pub fn snapshot(&self) -> EnvSnapshot {
+        let mut copies = HashMap::new();
+        let values = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), deep_clone(value, &mut copies)))
+            .collect();
+        EnvSnapshot { values }
+    }
+
+    // replaces this scope's bindings with the snapshot's, dropping any defined since
+    pub fn restore(&mut self, snapshot: EnvSnapshot) {
+        self.values = snapshot.values;
+    }
+}
+
+// copies keyed by the address of the original, so aliasing and cycles survive the clone
+fn deep_clone(value: &Value, copies: &mut HashMap<usize, Value>) -> Value {
+    let key = match value {
+        Value::List(list) => Rc::as_ptr(list) as usize,
+        Value::Map(map) => Rc::as_ptr(map) as usize,
+        Value::Instance(instance) => Rc::as_ptr(instance) as usize,
+        _ => return value.clone(),
+    };
+    if let Some(copy) = copies.get(&key) {
+        return copy.clone();
+    }
+    match value {
+        Value::List(list) => {
+            let copy = Rc::new(RefCell::new(Vec::new()));
+            copies.insert(key, Value::List(Rc::clone(&copy)));
+            let items = list
+                .borrow()
+                .iter()
+                .map(|item| deep_clone(item, copies))
+                .collect();
+            *copy.borrow_mut() = items;
+            Value::List(copy)
+        }
+        Value::Map(map) => {
+            let copy = Rc::new(RefCell::new(Map::default()));
+            copies.insert(key, Value::Map(Rc::clone(&copy)));
+            for (k, v) in map.borrow().iter() {
+                let v = deep_clone(v, copies);
+                copy.borrow_mut().insert(k.clone(), v);
+            }
+            Value::Map(copy)
+        }
+        Value::Instance(instance) => {
+            let copy = Rc::new(RefCell::new(Instance {
+                class: Rc::clone(&instance.borrow().class),
+                fields: HashMap::new(),
+            }));
+            copies.insert(key, Value::Instance(Rc::clone(&copy)));
+            for (name, v) in instance.borrow().fields.iter() {
+                let v = deep_clone(v, copies);
+                copy.borrow_mut().fields.insert(name.clone(), v);
+            }
+            Value::Instance(copy)
+        }
+        _ => unreachable!("only containers are deep cloned"),
+    }
+}
+
+fn undefined_variable(name: &str, span: Span) -> RuntimeError {
+    RuntimeError {
+        span,
+        message: format!("Undefined variable '{}'.", name),
+    }
+}
+
+// non-local exits that propagate up through statement execution
+enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+    Break,
+    Continue,
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Unwind {
+        Unwind::Error(error)
+    }
+}
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    env: Rc<RefCell<Environment>>,
+    locals: HashMap<ExprId, usize>,
+    // where print statements write to
+    out: Box<dyn Write>,
+    // where read_line reads from, or stdin, whose buffer is shared with the REPL, if None
+    input: Option<Box<dyn BufRead>>,
+    // calls currently in progress, and how many may be before "Stack overflow."
+    depth: usize,
+    max_depth: usize,
+    // whether read_file and write_file may touch the file system
+    file_access: bool,
+    // whether run_with parses programs rejecting a var without an initializer
+    strict_declarations: bool,
+    // where a line per executed statement goes, if tracing
+    trace: Option<Box<dyn Write>>,
+    // how many more statements and expressions may be evaluated, if limited
+    gas: Option<u64>,
+}
+
+// low enough that the Rust stack, which every Lox call recurses on, doesn't overflow first
+// even in unoptimized builds
+const DEFAULT_MAX_DEPTH: usize = 256;
+
+// traced values are cut off after this many chars
+const TRACE_VALUE_LEN: usize = 40;
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter::new_with_writer(io::stdout())
+    }
+
+    // print writes to out rather than stdout, e.g. to capture a program's output
+    pub fn new_with_writer(out: impl Write + 'static) -> Interpreter {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let mut interpreter = Interpreter {
+            env: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            out: Box::new(out),
+            input: None,
+            depth: 0,
+            file_access: false,
+            strict_declarations: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            trace: None,
+            gas: None,
+        };
+        interpreter.define_native("clock", 0, |_, _| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| e.to_string())?;
+            Ok(Value::Number(now.as_secs_f64()))
+        });
+        interpreter.define_native("len", 1, |_, args| match &args[0] {
+            Value::List(list) => Ok(Value::Number(list.borrow().len() as f64)),
+            Value::Map(map) => Ok(Value::Number(map.borrow().len() as f64)),
+            _ => Err("Argument must be a list or map.".to_string()),
+        });
+        interpreter.define_native("push", 2, |_, args| match &args[0] {
+            Value::List(list) => {
+                list.borrow_mut().push(args[1].clone());
+                Ok(Value::Nil)
+            }
+            _ => Err("Argument must be a list.".to_string()),
+        });
+        interpreter.define_native("pop", 1, |_, args| match &args[0] {
+            Value::List(list) => list
+                .borrow_mut()
+                .pop()
+                .ok_or_else(|| "Can't pop from an empty list.".to_string()),
+            _ => Err("Argument must be a list.".to_string()),
+        });
+        interpreter.define_native("str_len", 1, |_, args| match &args[0] {
+            Value::Str(s) => Ok(Value::Number(s.chars().count() as f64)),
+            _ => Err("Argument must be a string.".to_string()),
+        });
+        // substring(s, start, len), counting Unicode scalar values
+        interpreter.define_native("substring", 3, |_, args| {
+            let (s, start, len) = match args {
+                [Value::Str(s), start, len] => match (start.as_f64(), len.as_f64()) {
+                    (Some(start), Some(len)) => (s, start, len),
+                    _ => return Err("Arguments must be a string and two numbers.".to_string()),
+                },
+                _ => return Err("Arguments must be a string and two numbers.".to_string()),
+            };
+            if start.fract() != 0.0 || len.fract() != 0.0 {
+                return Err("Substring bounds must be integers.".to_string());
+            }
+            if start < 0.0 || len < 0.0 || start + len > s.chars().count() as f64 {
+                return Err("Substring out of range.".to_string());
+            }
+            Ok(Value::Str(
+                s.chars().skip(start as usize).take(len as usize).collect(),
+            ))
+        });
+        interpreter.define_native("type", 1, |_, args| {
+            Ok(Value::Str(args[0].type_name().to_string()))
+        });
+        interpreter.define_native("to_string", 1, |_, args| {
+            Ok(Value::Str(args[0].to_string()))
+        });
+        // nil rather than an error when s isn't a number literal, so callers can test for it
+        interpreter.define_native("to_number", 1, |_, args| match &args[0] {
+            Value::Str(s) => Ok(Scanner::parse_number(s).map_or(Value::Nil, Value::Number)),
+            _ => Err("Argument must be a string.".to_string()),
+        });
+        // the line without its newline, or nil at end of input
+        interpreter.define_native("read_line", 0, |interpreter, _| {
+            let mut line = String::new();
+            let read = match &mut interpreter.input {
+                Some(input) => input.read_line(&mut line),
+                None => io::stdin().read_line(&mut line),
+            };
+            match read.map_err(|e| e.to_string())? {
+                0 => Ok(Value::Nil),
+                _ => {
+                    let end = line.trim_end_matches(['\n', '\r']).len();
+                    line.truncate(end);
+                    Ok(Value::Str(line))
+                }
+            }
+        });
+        interpreter.define_native("read_file", 1, |interpreter, args| {
+            let path = file_path(interpreter, &args[0])?;
+            std::fs::read_to_string(path)
+                .map(Value::Str)
+                .map_err(|e| format!("Can't read '{}': {}.", path, e))
+        });
+        interpreter.define_native("write_file", 2, |interpreter, args| {
+            let path = file_path(interpreter, &args[0])?;
+            let Value::Str(contents) = &args[1] else {
+                return Err("Contents must be a string.".to_string());
+            };
+            std::fs::write(path, contents)
+                .map(|()| Value::Nil)
+                .map_err(|e| format!("Can't write '{}': {}.", path, e))
+        });
+        interpreter.define_native("assert", 1, |_, args| {
+            if args[0].is_truthy() {
+                Ok(Value::Nil)
+            } else {
+                Err(format!("Assertion failed: {} is not truthy.", args[0]))
+            }
+        });
+        interpreter.define_native("assertEq", 2, |_, args| {
+            if args[0] == args[1] {
+                Ok(Value::Nil)
+            } else {
+                // as source, so "1" and 1 can be told apart
+                Err(format!(
+                    "Assertion failed: {} != {}.",
+                    args[0].repr(),
+                    args[1].repr()
+                ))
+            }
+        });
+        interpreter
+    }
+
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&mut Interpreter, &[Value]) -> Result<Value, String> + 'static,
+    ) {
+        let native = Native {
+            name: name.to_string(),
+            arity,
+            func: Box::new(func),
+        };
+        self.globals
+            .borrow_mut()
+            .define(name, Value::Native(Rc::new(native)));
+    }
+
+    // limits how deeply calls may nest; more than a few hundred risks overflowing the Rust stack
+    // unless the interpreter runs on a thread with a larger one
+    pub fn with_max_depth(mut self, max_depth: usize) -> Interpreter {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // lets read_file and write_file read and overwrite any file the host process can, so only
+    // turn this on for trusted scripts; without it they fail with a runtime error
+    pub fn with_file_access(mut self, enabled: bool) -> Interpreter {
+        self.file_access = enabled;
+        self
+    }
+
+    // programs run with run_with are parsed with Parser::with_strict_declarations
+    pub fn with_strict_declarations(mut self, enabled: bool) -> Interpreter {
+        self.strict_declarations = enabled;
+        self
+    }
+
+    pub(crate) fn strict_declarations(&self) -> bool {
+        self.strict_declarations
+    }
+
+    // read_line reads from input rather than stdin
+    pub fn with_input(mut self, input: impl BufRead + 'static) -> Interpreter {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    // traces each executed statement to stderr, e.g. "[line 2] var x => 3"
+    pub fn with_trace(mut self, enabled: bool) -> Interpreter {
+        self.trace = enabled.then(|| Box::new(io::stderr()) as Box<dyn Write>);
+        self
+    }
+
+    // traces each executed statement to out rather than stderr
+    pub fn with_trace_writer(mut self, out: impl Write + 'static) -> Interpreter {
+        self.trace = Some(Box::new(out));
+        self
+    }
+
+    // fails with "Budget exceeded." once gas statements and expressions have been evaluated,
+    // so untrusted scripts can't run forever
+    pub fn with_gas(mut self, gas: u64) -> Interpreter {
+        self.gas = Some(gas);
+        self
+    }
+
+    // the global scope, e.g. for a host to snapshot and restore around running a script
+    pub fn globals(&self) -> Rc<RefCell<Environment>> {
+        Rc::clone(&self.globals)
+    }
+
+    // records scope distances from the Resolver; must happen before interpret
+    pub fn resolve(&mut self, locals: HashMap<ExprId, usize>) {
+        self.locals.extend(locals);
+    }
+
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
+        for stmt in stmts {
+            match self.execute(stmt) {
+                Ok(()) => {}
+                Err(Unwind::Error(error)) => return Err(error),
+                // a top-level return just ends the program; the resolver rejects stray loop control
+                Err(Unwind::Return(_) | Unwind::Break | Unwind::Continue) => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        self.consume_gas(|| stmt.span())?;
+        // statements producing a value are traced once it's known, the rest on entry so they
+        // come before any statements nested in them
+        if !matches!(
+            stmt,
+            Stmt::Expression(_) | Stmt::Print { .. } | Stmt::Var { .. } | Stmt::Return { .. }
+        ) {
+            self.trace(stmt, None)?;
+        }
+        match stmt {
+            Stmt::Expression(expr) => {
+                let value = self.eval(expr)?;
+                self.trace(stmt, Some(&value))?;
+            }
+            Stmt::Print { expr, .. } => {
+                let value = self.eval(expr)?;
+                self.trace(stmt, Some(&value))?;
+                writeln!(self.out, "{}", value).map_err(|error| RuntimeError {
+                    span: stmt.span(),
+                    message: format!("Can't write output: {}.", error),
+                })?;
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let value = match initializer {
+                    Some(expr) => self.eval(expr)?,
+                    None => Value::Nil,
+                };
+                self.trace(stmt, Some(&value))?;
+                self.env.borrow_mut().define(name, value);
+            }
+            Stmt::Block { stmts, .. } => {
+                let env = Environment::with_enclosing(Rc::clone(&self.env));
+                self.execute_block(stmts, Rc::new(RefCell::new(env)))?;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                if self.eval(condition)?.is_truthy() {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                while self.eval(condition)?.is_truthy() {
+                    match self.execute(body) {
+                        Ok(()) | Err(Unwind::Continue) => {}
+                        Err(Unwind::Break) => break,
+                        Err(unwind) => return Err(unwind),
+                    }
+                    if let Some(increment) = increment {
+                        self.eval(increment)?;
+                    }
+                }
+            }
+            Stmt::Break { .. } => return Err(Unwind::Break),
+            Stmt::Continue { .. } => return Err(Unwind::Continue),
+            Stmt::Function(decl) => {
+                let fun = Function {
+                    decl: Rc::clone(decl),
+                    closure: Rc::clone(&self.env),
+                    is_initializer: false,
+                };
+                self.env
+                    .borrow_mut()
+                    .define(&decl.name, Value::Function(Rc::new(fun)));
+            }
+            Stmt::Class {
+                name,
+                span,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(expr) => match self.eval(expr)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            return Err(RuntimeError {
+                                span: *span,
+                                message: "Superclass must be a class.".to_string(),
+                            }
+                            .into())
+                        }
+                    },
+                    None => None,
+                };
+                // methods of a subclass close over an environment binding `super`
+                let closure = match &superclass {
+                    Some(superclass) => {
+                        let mut env = Environment::with_enclosing(Rc::clone(&self.env));
+                        env.define("super", Value::Class(Rc::clone(superclass)));
+                        Rc::new(RefCell::new(env))
+                    }
+                    None => Rc::clone(&self.env),
+                };
+                let methods = methods
+                    .iter()
+                    .map(|decl| {
+                        let method = Function {
+                            decl: Rc::clone(decl),
+                            closure: Rc::clone(&closure),
+                            is_initializer: decl.name == "init",
+                        };
+                        (decl.name.clone(), Rc::new(method))
+                    })
+                    .collect();
+                let class = Class {
+                    name: name.clone(),
+                    superclass,
+                    methods,
+                };
+                self.env
+                    .borrow_mut()
+                    .define(name, Value::Class(Rc::new(class)));
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(expr) => self.eval(expr)?,
+                    None => Value::Nil,
+                };
+                self.trace(stmt, Some(&value))?;
+                return Err(Unwind::Return(value));
+            }
+        }
+        Ok(())
+    }
+
+    // span is only worked out when the budget has run out
+    fn consume_gas(&mut self, span: impl FnOnce() -> Span) -> Result<(), RuntimeError> {
+        match &mut self.gas {
+            Some(0) => Err(RuntimeError {
+                span: span(),
+                message: "Budget exceeded.".to_string(),
+            }),
+            Some(gas) => {
+                *gas -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // traced at the statement's own line, since a statement traced after its value is known may
+    // have run others, like the body of a function it called, in the meantime
+    fn trace(&mut self, stmt: &Stmt, value: Option<&Value>) -> Result<(), RuntimeError> {
+        let Some(out) = &mut self.trace else {
+            return Ok(());
+        };
+        let line = stmt.span().line;
+        let kind = match stmt {
+            Stmt::Expression(_) => "expression".to_string(),
+            Stmt::Print { .. } => "print".to_string(),
+            Stmt::Var { name, .. } => format!("var {}", name),
+            Stmt::Block { .. } => "block".to_string(),
+            Stmt::If { .. } => "if".to_string(),
+            Stmt::While { .. } => "while".to_string(),
+            Stmt::Break { .. } => "break".to_string(),
+            Stmt::Continue { .. } => "continue".to_string(),
+            Stmt::Function(decl) => format!("fun {}", decl.name),
+            Stmt::Return { .. } => "return".to_string(),
+            Stmt::Class { name, .. } => format!("class {}", name),
+        };
+        let result = match value {
+            Some(value) => {
+                let value = value.to_string();
+                let summary = match value.char_indices().nth(TRACE_VALUE_LEN) {
+                    Some((end, _)) => format!("{}...", &value[..end]),
+                    None => value,
+                };
+                writeln!(out, "[line {}] {} => {}", line, kind, summary)
+            }
+            None => writeln!(out, "[line {}] {}", line, kind),
+        };
+        result.map_err(|error| RuntimeError {
+            span: stmt.span(),
+            message: format!("Can't write trace: {}.", error),
+        })
+    }
+
+    fn execute_block(
+        &mut self,
+        stmts: &[Stmt],
+        env: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let prev = std::mem::replace(&mut self.env, env);
+        let result = stmts.iter().try_for_each(|stmt| self.execute(stmt));
+        self.env = prev;
+        result
+    }
+
+    // calls anything callable, reporting errors at span as a call expression there would
+    pub fn call(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        if self.depth >= self.max_depth {
+            return Err(RuntimeError {
+                span,
+                message: "Stack overflow.".to_string(),
+            });
+        }
+        self.depth += 1;
+        let result = self.call_value(callee, args, span);
+        self.depth -= 1;
+        result
+    }
+
+    fn call_value(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::Function(fun) => {
+                check_arity(fun.arity(), args.len(), span)?;
+                self.call_function(&fun, args)
+            }
+            Value::Native(native) => {
+                check_arity(native.arity, args.len(), span)?;
+                (native.func)(self, &args).map_err(|message| RuntimeError { span, message })
+            }
+            Value::Class(class) => {
+                check_arity(class.arity(), args.len(), span)?;
+                let instance = Rc::new(RefCell::new(Instance {
+                    class: Rc::clone(&class),
+                    fields: HashMap::new(),
+                }));
+                if let Some(init) = class.find_method("init") {
+                    self.call_function(&init.bind(Rc::clone(&instance)), args)?;
+                }
+                Ok(Value::Instance(instance))
+            }
+            _ => Err(RuntimeError {
+                span,
+                message: "Can only call functions and classes.".to_string(),
+            }),
+        }
+    }
+
+    fn call_function(&mut self, fun: &Function, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut env = Environment::with_enclosing(Rc::clone(&fun.closure));
+        for ((param, _), arg) in fun.decl.params.iter().zip(args) {
+            env.define(param, arg);
+        }
+        let value = match self.execute_block(&fun.decl.body, Rc::new(RefCell::new(env))) {
+            Ok(()) | Err(Unwind::Break | Unwind::Continue) => Value::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(error)) => return Err(error),
+        };
+        // initializers always return the instance, even from a bare return
+        if fun.is_initializer {
+            fun.closure.borrow().get("this", fun.decl.span)
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn assign_variable(
+        &mut self,
+        id: ExprId,
+        name: &str,
+        value: Value,
+        span: Span,
+    ) -> Result<(), RuntimeError> {
+        match self.locals.get(&id) {
+            Some(&distance) => self.env.borrow_mut().assign_at(distance, name, value, span),
+            None => self.globals.borrow_mut().assign(name, value, span),
+        }
+    }
+
+    fn look_up_variable(&self, id: ExprId, name: &str, span: Span) -> Result<Value, RuntimeError> {
+        match self.locals.get(&id) {
+            Some(&distance) => self.env.borrow().get_at(distance, name, span),
+            None => self.globals.borrow().get(name, span),
+        }
+    }
+
+    pub fn eval(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        self.consume_gas(|| expr.span())?;
+        match expr {
+            Expr::Literal { value, .. } => Ok(match value {
+                Literal::Number(n) => Value::Number(*n),
+                Literal::Int(n) => Value::Int(*n),
+                Literal::Str(s) => Value::Str(s.clone()),
+                Literal::Bool(b) => Value::Bool(*b),
+                Literal::Nil => Value::Nil,
+            }),
+            Expr::Variable { id, name, span } => self.look_up_variable(*id, name, *span),
+            Expr::Assign {
+                id,
+                name,
+                span,
+                value,
+            } => {
+                let value = self.eval(value)?;
+                self.assign_variable(*id, name, value.clone(), *span)?;
+                Ok(value)
+            }
+            Expr::List { elements, .. } => {
+                let values = elements
+                    .iter()
+                    .map(|element| self.eval(element))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::List(Rc::new(RefCell::new(values))))
+            }
+            Expr::Map { entries, .. } => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    let key = map_key(&self.eval(key)?, key.span())?;
+                    map.insert(key, self.eval(value)?);
+                }
+                Ok(Value::Map(Rc::new(RefCell::new(map))))
+            }
+            Expr::Index {
+                object,
+                span,
+                index,
+            } => {
+                let object = self.eval(object)?;
+                let index = self.eval(index)?;
+                get_index(&object, &index, *span)
+            }
+            Expr::SetIndex {
+                object,
+                span,
+                index,
+                value,
+            } => {
+                let object = self.eval(object)?;
+                let index = self.eval(index)?;
+                let value = self.eval(value)?;
+                set_index(&object, &index, value.clone(), *span)?;
+                Ok(value)
+            }
+            Expr::Update {
+                op,
+                prefix,
+                span,
+                target,
+            } => {
+                let delta = match op {
+                    UpdateOp::Inc => 1,
+                    UpdateOp::Dec => -1,
+                };
+                // an int stays an int, overflow included
+                let step = |value: Value| match value {
+                    Value::Number(_) | Value::Int(_) => {
+                        let new = binary(value.clone(), BinaryOp::Add, Value::Int(delta), *span)?;
+                        Ok((value, new))
+                    }
+                    _ => Err(RuntimeError {
+                        span: *span,
+                        message: "Operand must be a number.".to_string(),
+                    }),
+                };
+                let (old, new) = match target.as_ref() {
+                    Expr::Variable { id, name, .. } => {
+                        let (old, new) = step(self.look_up_variable(*id, name, *span)?)?;
+                        self.assign_variable(*id, name, new.clone(), *span)?;
+                        (old, new)
+                    }
+                    Expr::Get { object, name, .. } => {
+                        let Value::Instance(instance) = self.eval(object)? else {
+                            return Err(RuntimeError {
+                                span: *span,
+                                message: "Only instances have fields.".to_string(),
+                            });
+                        };
+                        let (old, new) = step(get_property(Rc::clone(&instance), name, *span)?)?;
+                        instance
+                            .borrow_mut()
+                            .fields
+                            .insert(name.clone(), new.clone());
+                        (old, new)
+                    }
+                    _ => unreachable!("the parser only builds updates of variables and properties"),
+                };
+                Ok(if *prefix { new } else { old })
+            }
+            Expr::Logical {
+                left, op, right, ..
+            } => {
+                let left = self.eval(left)?;
+                // the deciding operand is returned as is, not coerced to a bool
+                match (op, left.is_truthy()) {
+                    (LogicalOp::Or, true) | (LogicalOp::And, false) => Ok(left),
+                    _ => self.eval(right),
+                }
+            }
+            Expr::Comma { left, right, .. } => {
+                self.eval(left)?;
+                self.eval(right)
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                if self.eval(condition)?.is_truthy() {
+                    self.eval(then_branch)
+                } else {
+                    self.eval(else_branch)
+                }
+            }
+            Expr::Lambda(decl) => Ok(Value::Function(Rc::new(Function {
+                decl: Rc::clone(decl),
+                closure: Rc::clone(&self.env),
+                is_initializer: false,
+            }))),
+            Expr::Call { callee, span, args } => {
+                let callee = self.eval(callee)?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<_, _>>()?;
+                self.call(callee, args, *span)
+            }
+            Expr::Get { object, name, span } => {
+                let Value::Instance(instance) = self.eval(object)? else {
+                    return Err(RuntimeError {
+                        span: *span,
+                        message: "Only instances have properties.".to_string(),
+                    });
+                };
+                get_property(instance, name, *span)
+            }
+            Expr::Set {
+                object,
+                name,
+                span,
+                value,
+            } => {
+                let Value::Instance(instance) = self.eval(object)? else {
+                    return Err(RuntimeError {
+                        span: *span,
+                        message: "Only instances have fields.".to_string(),
+                    });
+                };
+                let value = self.eval(value)?;
+                instance
+                    .borrow_mut()
+                    .fields
+                    .insert(name.clone(), value.clone());
+                Ok(value)
+            }
+            Expr::CompoundSet {
+                object,
+                name,
+                span,
+                op,
+                value,
+            } => {
+                let Value::Instance(instance) = self.eval(object)? else {
+                    return Err(RuntimeError {
+                        span: *span,
+                        message: "Only instances have fields.".to_string(),
+                    });
+                };
+                let current = get_property(Rc::clone(&instance), name, *span)?;
+                let value = self.eval(value)?;
+                let value = binary(current, *op, value, *span)?;
+                instance
+                    .borrow_mut()
+                    .fields
+                    .insert(name.clone(), value.clone());
+                Ok(value)
+            }
+            Expr::This { id, span } => self.look_up_variable(*id, "this", *span),
+            Expr::Super { id, span, method } => {
+                let distance = self.locals[id];
+                let env = self.env.borrow();
+                let Value::Class(superclass) = env.get_at(distance, "super", *span)? else {
+                    unreachable!("super is only ever bound to a class");
+                };
+                // `this` is bound in the scope just inside the one binding `super`
+                let Value::Instance(instance) = env.get_at(distance - 1, "this", *span)? else {
+                    unreachable!("this is only ever bound to an instance");
+                };
+                match superclass.find_method(method) {
+                    Some(method) => Ok(Value::Function(Rc::new(method.bind(instance)))),
+                    None => Err(RuntimeError {
+                        span: *span,
+                        message: format!("Undefined property '{}'.", method),
+                    }),
+                }
+            }
+            Expr::Grouping { expr, .. } => self.eval(expr),
+            Expr::Unary { op, right, .. } => {
+                let value = self.eval(right)?;
+                unary(*op, right.span(), value)
+            }
+            Expr::Binary {
+                left,
+                op,
+                span,
+                right,
+            } => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                binary(left, *op, right, *span)
+            }
+        }
+    }
+}
+
+fn get_index(object: &Value, index: &Value, span: Span) -> Result<Value, RuntimeError> {
+    match object {
+        Value::List(list) => {
+            let list = list.borrow();
+            Ok(list[list_index(index, list.len(), "List", span)?].clone())
+        }
+        // strings are indexed by Unicode scalar value, not by byte
+        Value::Str(s) => {
+            let i = list_index(index, s.chars().count(), "String", span)?;
+            Ok(Value::Str(s.chars().nth(i).unwrap().to_string()))
+        }
+        Value::Map(map) => map
+            .borrow()
+            .get(&map_key(index, span)?)
+            .cloned()
+            .ok_or_else(|| RuntimeError {
+                span,
+                message: "Key not found.".to_string(),
+            }),
+        _ => Err(RuntimeError {
+            span,
+            message: "Only lists, maps and strings can be indexed.".to_string(),
+        }),
+    }
+}
+
+fn set_index(object: &Value, index: &Value, value: Value, span: Span) -> Result<(), RuntimeError> {
+    match object {
+        Value::List(list) => {
+            let mut list = list.borrow_mut();
+            let i = list_index(index, list.len(), "List", span)?;
+            list[i] = value;
+        }
+        Value::Map(map) => map.borrow_mut().insert(map_key(index, span)?, value),
+        Value::Str(_) => {
+            return Err(RuntimeError {
+                span,
+                message: "Strings are immutable.".to_string(),
+            })
+        }
+        _ => {
+            return Err(RuntimeError {
+                span,
+                message: "Only lists and maps can be indexed.".to_string(),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn map_key(key: &Value, span: Span) -> Result<MapKey, RuntimeError> {
+    MapKey::new(key).ok_or_else(|| RuntimeError {
+        span,
+        message: "Map keys must be strings or numbers.".to_string(),
+    })
+}
+
+// checks index is a whole number within a list of len elements
+// kind names what's being indexed in the error message
+fn list_index(index: &Value, len: usize, kind: &str, span: Span) -> Result<usize, RuntimeError> {
+    let problem = match index.as_f64() {
+        Some(n) if n.fract() != 0.0 => "must be an integer",
+        Some(n) if n < 0.0 || n >= len as f64 => "out of range",
+        Some(n) => return Ok(n as usize),
+        None => "must be a number",
+    };
+    Err(RuntimeError {
+        span,
+        message: format!("{} index {}.", kind, problem),
+    })
+}
+
+// a field, or failing that a method bound to instance
+fn get_property(
+    instance: Rc<RefCell<Instance>>,
+    name: &str,
+    span: Span,
+) -> Result<Value, RuntimeError> {
+    if let Some(value) = instance.borrow().fields.get(name) {
+        return Ok(value.clone());
+    }
+    let class = Rc::clone(&instance.borrow().class);
+    match class.find_method(name) {
+        Some(method) => Ok(Value::Function(Rc::new(method.bind(instance)))),
+        None => Err(RuntimeError {
+            span,
+            message: format!("Undefined property '{}'.", name),
+        }),
+    }
+}
+
+fn file_path<'a>(interpreter: &Interpreter, path: &'a Value) -> Result<&'a str, String> {
+    if !interpreter.file_access {
+        return Err("File access is disabled.".to_string());
+    }
+    match path {
+        Value::Str(path) => Ok(path),
+        _ => Err("Path must be a string.".to_string()),
+    }
+}
+
+fn check_arity(arity: usize, args: usize, span: Span) -> Result<(), RuntimeError> {
+    if arity == args {
+        Ok(())
+    } else {
+        Err(RuntimeError {
+            span,
+            message: format!("Expected {} arguments but got {}.", arity, args),
+        })
+    }
+}
+
+pub(crate) fn unary(op: UnaryOp, span: Span, right: Value) -> Result<Value, RuntimeError> {
+    let error = |message: &str| RuntimeError {
+        span,
+        message: message.to_string(),
+    };
+    match op {
+        UnaryOp::Not => Ok(Value::Bool(!right.is_truthy())),
+        UnaryOp::ToStr => Ok(Value::Str(right.to_string())),
+        UnaryOp::Neg => match right {
+            Value::Number(n) => Ok(Value::Number(-n)),
+            Value::Int(n) => n
+                .checked_neg()
+                .map(Value::Int)
+                .ok_or_else(|| error("Integer overflow.")),
+            _ => Err(error("Operand must be a number.")),
+        },
+        UnaryOp::BitNot => match (&right, integer(&right)) {
+            (Value::Int(_), Some(n)) => Ok(Value::Int(!n)),
+            (_, Some(n)) => Ok(Value::Number(!n as f64)),
+            (_, None) => Err(error("Operand must be an integer.")),
+        },
+    }
+}
+
+// span is the operator's, which every error in the operation points at
+pub(crate) fn binary(
+    left: Value,
+    op: BinaryOp,
+    right: Value,
+    span: Span,
+) -> Result<Value, RuntimeError> {
+    let value = match (op, left, right) {
+        (BinaryOp::Equal, left, right) => Value::Bool(left == right),
+        (BinaryOp::NotEqual, left, right) => Value::Bool(left != right),
+        (BinaryOp::Add, Value::Str(a), Value::Str(b)) => Value::Str(a + &b),
+        (op, Value::Int(a), Value::Int(b)) => int_binary(a, op, b, span)?,
+        // on floats, only those holding integers in i64's range
+        (
+            op @ (BinaryOp::BitAnd
+            | BinaryOp::BitOr
+            | BinaryOp::BitXor
+            | BinaryOp::Shl
+            | BinaryOp::Shr),
+            left,
+            right,
+        ) => {
+            let (Some(a), Some(b)) = (integer(&left), integer(&right)) else {
+                return Err(RuntimeError {
+                    span,
+                    message: "Operands must be integers.".to_string(),
+                });
+            };
+            Value::Number(bitwise(a, op, b, span)? as f64)
+        }
+        (op, left, right) => {
+            let (Some(a), Some(b)) = (left.as_f64(), right.as_f64()) else {
+                let message = match op {
+                    BinaryOp::Add => "Operands must be two numbers or two strings.",
+                    _ => "Operands must be numbers.",
+                };
+                return Err(RuntimeError {
+                    span,
+                    message: message.to_string(),
+                });
+            };
+            match op {
+                BinaryOp::Add => Value::Number(a + b),
+                BinaryOp::Sub => Value::Number(a - b),
+                BinaryOp::Mul => Value::Number(a * b),
+                BinaryOp::Div => Value::Number(a / b),
+                // the sign follows the dividend, and x % 0 is NaN
+                BinaryOp::Rem => Value::Number(a % b),
+                BinaryOp::Pow => Value::Number(a.powf(b)),
+                BinaryOp::Less => Value::Bool(a < b),
+                BinaryOp::LessEqual => Value::Bool(a <= b),
+                BinaryOp::Greater => Value::Bool(a > b),
+                BinaryOp::GreaterEqual => Value::Bool(a >= b),
+                BinaryOp::Equal
+                | BinaryOp::NotEqual
+                | BinaryOp::BitAnd
+                | BinaryOp::BitOr
+                | BinaryOp::BitXor
+                | BinaryOp::Shl
+                | BinaryOp::Shr => unreachable!(),
+            }
+        }
+    };
+    Ok(value)
+}
+
+// shifts are arithmetic, and by less than 64 bits
+fn bitwise(a: i64, op: BinaryOp, b: i64, span: Span) -> Result<i64, RuntimeError> {
+    let shift = || {
+        u32::try_from(b)
+            .ok()
+            .filter(|&b| b < 64)
+            .ok_or_else(|| RuntimeError {
+                span,
+                message: "Shift amount out of range.".to_string(),
+            })
+    };
+    let result = match op {
+        BinaryOp::BitAnd => a & b,
+        BinaryOp::BitOr => a | b,
+        BinaryOp::BitXor => a ^ b,
+        BinaryOp::Shl => a << shift()?,
+        BinaryOp::Shr => a >> shift()?,
+        _ => unreachable!("only bitwise operators are passed here"),
+    };
+    Ok(result)
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match *value {
+        Value::Int(n) => Some(n),
+        Value::Number(n) if n.fract() == 0.0 && (-(2f64.powi(63))..2f64.powi(63)).contains(&n) => {
+            Some(n as i64)
+        }
+        _ => None,
+    }
+}
+
+// exact arithmetic that reports overflow rather than wrapping; division truncates toward zero
+fn int_binary(a: i64, op: BinaryOp, b: i64, span: Span) -> Result<Value, RuntimeError> {
+    if matches!(op, BinaryOp::Div | BinaryOp::Rem) && b == 0 {
+        return Err(RuntimeError {
+            span,
+            message: "Division by zero.".to_string(),
+        });
+    }
+    let result = match op {
+        BinaryOp::Add => a.checked_add(b),
+        BinaryOp::Sub => a.checked_sub(b),
+        BinaryOp::Mul => a.checked_mul(b),
+        BinaryOp::Div => a.checked_div(b),
+        BinaryOp::Rem => a.checked_rem(b),
+        // a negative exponent gives a fraction, so it's done in floating point
+        BinaryOp::Pow if b < 0 => return Ok(Value::Number((a as f64).powf(b as f64))),
+        BinaryOp::Pow => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+        BinaryOp::Less => return Ok(Value::Bool(a < b)),
+        BinaryOp::LessEqual => return Ok(Value::Bool(a <= b)),
+        BinaryOp::Greater => return Ok(Value::Bool(a > b)),
+        BinaryOp::GreaterEqual => return Ok(Value::Bool(a >= b)),
+        BinaryOp::Equal => return Ok(Value::Bool(a == b)),
+        BinaryOp::NotEqual => return Ok(Value::Bool(a != b)),
+        BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::Shl | BinaryOp::Shr => {
+            return bitwise(a, op, b, span).map(Value::Int)
+        }
+    };
+    result.map(Value::Int).ok_or_else(|| RuntimeError {
+        span,
+        message: "Integer overflow.".to_string(),
+    })
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Error: {}", self.span.line, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_to_string, LoxError};
+
+    fn run(src: &str) -> String {
+        run_to_string(src).unwrap()
+    }
+
+    fn error(src: &str) -> RuntimeError {
+        match run_to_string(src) {
+            Err(LoxError::Runtime(error)) => error,
+            result => panic!("expected a runtime error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert_eq!(run("print (1 + 2) * 3 - 4 / 2;"), "7\n");
+        assert_eq!(run("print -1.5;"), "-1.5\n");
+    }
+
+    #[test]
+    fn concatenates_strings() {
+        assert_eq!(run("print \"foo\" + \"bar\";"), "foobar\n");
+    }
+
+    #[test]
+    fn compares_values() {
+        assert_eq!(
+            run("print 1 < 2; print 2 <= 1; print \"a\" == \"a\";"),
+            "true\nfalse\ntrue\n"
+        );
+        assert_eq!(run("print nil == false; print !nil;"), "false\ntrue\n");
+    }
+
+    #[test]
+    fn type_mismatch_is_reported_on_the_operator_line() {
+        let error = error("print 1\n- \"a\";");
+        assert_eq!(error.message, "Operands must be numbers.");
+        assert_eq!(error.span.line, 2);
+    }
+
+    #[test]
+    fn type_mismatch_is_not_reported_on_the_operand_line() {
+        let src = "print 1 -\n\"a\";";
+        let error = error(src);
+        assert_eq!(error.span.line, 1);
+        assert_eq!(error.span.text(src), "-");
+    }
+
+    #[test]
+    fn inner_blocks_shadow_outer_variables() {
+        let src = "var a = 1; { var a = 2; { var a = 3; print a; } print a; } print a;";
+        assert_eq!(run(src), "3\n2\n1\n");
+    }
+
+    #[test]
+    fn assignment_mutates_the_nearest_binding() {
+        let src = "var a = 1; { var b = 0; { a = 2; b = 3; } print b; } print a;";
+        assert_eq!(run(src), "3\n2\n");
+        assert_eq!(
+            error("{ var c = 1; } c = 2;").message,
+            "Undefined variable 'c'."
+        );
+    }
+
+    #[test]
+    fn for_loop_counts() {
+        assert_eq!(
+            run("for (var i = 0; i < 5; i = i + 1) print i;"),
+            "0\n1\n2\n3\n4\n"
+        );
+    }
+
+    #[test]
+    fn if_selects_the_branch_by_truthiness() {
+        assert_eq!(run("if (0) print \"then\"; else print \"else\";"), "then\n");
+        assert_eq!(
+            run("if (nil) print \"then\"; else print \"else\";"),
+            "else\n"
+        );
+        assert_eq!(run("var n = 0; while (n < 3) n = n + 1; print n;"), "3\n");
+    }
+
+    #[test]
+    fn for_without_condition_loops_until_break() {
+        assert_eq!(
+            run("var n = 0; for (;;) { n = n + 1; if (n == 4) break; } print n;"),
+            "4\n"
+        );
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        let src = "var calls = 0;
+            fun touch() { calls = calls + 1; return true; }
+            print false and touch();
+            print 1 or touch();
+            print calls;
+            print nil or touch();
+            print calls;";
+        assert_eq!(run(src), "false\n1\n0\ntrue\n1\n");
+    }
+
+    #[test]
+    fn logical_operators_return_an_operand() {
+        assert_eq!(
+            run("print nil or \"default\"; print 1 and 2;"),
+            "default\n2\n"
+        );
+    }
+
+    #[test]
+    fn recursive_function() {
+        let src =
+            "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(10);";
+        assert_eq!(run(src), "55\n");
+    }
+
+    #[test]
+    fn closure_keeps_its_captured_variable() {
+        let src = "fun counter() { var n = 0; fun inc() { n = n + 1; return n; } return inc; }
+            var c = counter();
+            c();
+            c();
+            print c();
+            print counter()();";
+        assert_eq!(run(src), "3\n1\n");
+    }
+
+    #[test]
+    fn arity_mismatch_is_an_error() {
+        let error = error("fun f(a, b) {}\nf(1);");
+        assert_eq!(error.message, "Expected 2 arguments but got 1.");
+        assert_eq!(error.span.line, 2);
+    }
+
+    #[test]
+    fn clock_returns_a_number() {
+        let mut interpreter = Interpreter::new_with_writer(io::sink());
+        let stmts = crate::Parser::new(crate::Scanner::new("clock();").scan().unwrap())
+            .parse_program()
+            .unwrap();
+        let Stmt::Expression(expr) = &stmts[0] else {
+            unreachable!();
+        };
+        assert!(matches!(interpreter.eval(expr), Ok(Value::Number(_))));
+    }
+
+    #[test]
+    fn embedder_can_define_a_native() {
+        let buf = crate::SharedBuf::default();
+        let mut interpreter = Interpreter::new_with_writer(buf.clone());
+        interpreter.define_native("echo", 1, |_, args| Ok(args[0].clone()));
+        crate::run_with(&mut interpreter, "print echo(\"hi\"); print echo(2);").unwrap();
+        assert_eq!(&*buf.0.borrow(), b"hi\n2\n");
+    }
+
+    #[test]
+    fn instances_have_fields_and_methods() {
+        let src = "class Point {
+                init(x) { this.x = x; }
+                describe() { return \"x=\" + to_string(this.x); }
+            }
+            var p = Point(1);
+            p.y = 2;
+            print p.y;
+            print p.describe();
+            print p;";
+        assert_eq!(run(src), "2\nx=1\nPoint instance\n");
+    }
+
+    #[test]
+    fn missing_property_is_an_error() {
+        assert_eq!(
+            error("class A {} A().x;").message,
+            "Undefined property 'x'."
+        );
+        assert_eq!(
+            error("class A { init(a) {} } A();").message,
+            "Expected 1 arguments but got 0."
+        );
+    }
+
+    #[test]
+    fn overridden_method_can_call_super() {
+        let src = "class A { name() { return \"A\"; } }
+            class B < A { name() { return \"B<\" + super.name(); } }
+            class C < B {}
+            print C().name();";
+        assert_eq!(run(src), "B<A\n");
+    }
+
+    #[test]
+    fn inheriting_from_itself_or_a_non_class_is_an_error() {
+        let Err(LoxError::Parse(errors)) = run_to_string("class A < A {}") else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(errors[0].message, "A class can't inherit from itself.");
+        assert_eq!(
+            error("var A = 1; class B < A {}").message,
+            "Superclass must be a class."
+        );
+    }
+
+    #[test]
+    fn ternary_nests_to_the_right() {
+        let src = "fun sign(n) { return n > 0 ? 1 : n < 0 ? -1 : 0; }
+            print sign(5); print sign(-5); print sign(0);";
+        assert_eq!(run(src), "1\n-1\n0\n");
+    }
+
+    #[test]
+    fn ternary_evaluates_only_the_taken_branch() {
+        let src = "var hit = \"\";
+            fun mark(s) { hit = hit + s; return s; }
+            print true ? mark(\"a\") : mark(\"b\");
+            print nil ? mark(\"c\") : mark(\"d\");
+            print hit;";
+        assert_eq!(run(src), "a\nd\nad\n");
+    }
+
+    #[test]
+    fn comma_evaluates_to_its_last_operand() {
+        assert_eq!(run("print (1, 2, 3);"), "3\n");
+        let src = "var a = 0; var b = (a = a + 1, a = a * 10, a + 1); print a; print b;";
+        assert_eq!(run(src), "10\n11\n");
+    }
+
+    #[test]
+    fn commas_in_arguments_separate_them() {
+        assert_eq!(run("fun f(a, b) { return b; } print f(1, 2);"), "2\n");
+        assert_eq!(run("fun f(a) { return a; } print f((1, 2));"), "2\n");
+    }
+
+    #[test]
+    fn break_leaves_only_the_innermost_loop() {
+        let src = "for (var i = 0; i < 3; i = i + 1) {
+                for (var j = 0; j < 3; j = j + 1) {
+                    if (j == 1) break;
+                    print i * 10 + j;
+                }
+            }";
+        assert_eq!(run(src), "0\n10\n20\n");
+    }
+
+    #[test]
+    fn continue_still_runs_the_increment() {
+        let src = "for (var i = 0; i < 5; i = i + 1) { if (i % 2 == 0) continue; print i; }";
+        assert_eq!(run(src), "1\n3\n");
+        let src = "var i = 0; while (i < 4) { i = i + 1; if (i == 2) continue; print i; }";
+        assert_eq!(run(src), "1\n3\n4\n");
+    }
+
+    #[test]
+    fn lambda_can_be_passed_to_a_native() {
+        let buf = crate::SharedBuf::default();
+        let mut interpreter = Interpreter::new_with_writer(buf.clone());
+        interpreter.define_native("map", 2, |interpreter, args| {
+            let Value::List(list) = &args[0] else {
+                return Err("Expected a list.".to_string());
+            };
+            let items = list.borrow().clone();
+            let mapped = items
+                .into_iter()
+                .map(|item| interpreter.call(args[1].clone(), vec![item], Span::default()))
+                .collect::<Result<_, _>>()
+                .map_err(|error| error.message)?;
+            Ok(Value::List(Rc::new(RefCell::new(mapped))))
+        });
+        let src = "print map([1, 2, 3], fun (x) { return x * x; });";
+        crate::run_with(&mut interpreter, src).unwrap();
+        assert_eq!(&*buf.0.borrow(), b"[1, 4, 9]\n");
+    }
+
+    #[test]
+    fn lambda_can_be_called_immediately() {
+        assert_eq!(run("print fun (a) { return a + 1; }(1);"), "2\n");
+        assert_eq!(run("fun named() { return 1; } print named();"), "1\n");
+    }
+
+    #[test]
+    fn type_errors_point_at_the_operator() {
+        let src = "var x = nil;\nprint 1 + x * 2;";
+        let nil_operand = error(src);
+        assert_eq!(nil_operand.span.text(src), "*");
+        let expected = "[line 2] Error: Operands must be numbers.\n  |\n\
+                        2 | print 1 + x * 2;\n  |             ^";
+        assert_eq!(
+            crate::render_diagnostic(src, nil_operand.span, &nil_operand.to_string()),
+            expected
+        );
+        let src = "print \"a\" + 1;";
+        assert_eq!(error(src).span.text(src), "+");
+    }
+
+    #[test]
+    fn remainder_follows_the_sign_of_the_dividend() {
+        assert_eq!(run("print 7 % 3 == 1;"), "true\n");
+        assert_eq!(run("print -7 % 3;"), "-1\n");
+        assert_eq!(run("print 7.5 % 2;"), "1.5\n");
+        assert_eq!(run("print 1 % 0;"), "NaN\n");
+    }
+
+    #[test]
+    fn remainder_of_a_non_number_is_an_error() {
+        let src = "print 7 % \"3\";";
+        let error = error(src);
+        assert_eq!(error.message, "Operands must be numbers.");
+        assert_eq!(error.span.text(src), "%");
+    }
+
+    #[test]
+    fn compound_assignment_updates_the_variable() {
+        assert_eq!(run("var x = 1; x += 4; print x;"), "5\n");
+        assert_eq!(run("var x = 10; x -= 4; x *= 3; x /= 2; print x;"), "9\n");
+        assert_eq!(run("var s = \"a\"; s += \"b\"; print s;"), "ab\n");
+    }
+
+    #[test]
+    fn compound_assignment_to_a_property_evaluates_the_object_once() {
+        let src = "class C {}\nvar c = C();\nc.v = 1;\nvar calls = 0;\n\
+                   fun get() { calls = calls + 1; return c; }\n\
+                   print get().v += 2;\nprint c.v;\nprint calls;";
+        assert_eq!(run(src), "3\n3\n1\n");
+    }
+
+    #[test]
+    fn postfix_increment_returns_the_old_value() {
+        assert_eq!(run("var x = 1; print x++; print x == 2;"), "1\ntrue\n");
+        assert_eq!(run("var x = 1; print x--; print x;"), "1\n0\n");
+    }
+
+    #[test]
+    fn prefix_increment_returns_the_new_value() {
+        assert_eq!(run("var x = 1; print ++x; print --x;"), "2\n1\n");
+        let src = "class C {}\nvar c = C();\nc.n = 5;\nprint c.n++;\nprint ++c.n;";
+        assert_eq!(run(src), "5\n7\n");
+    }
+
+    #[test]
+    fn incrementing_a_non_number_is_an_error() {
+        assert_eq!(
+            error("var s = \"a\";\ns++;").message,
+            "Operand must be a number."
+        );
+    }
+
+    #[test]
+    fn lists_can_be_built_indexed_and_mutated() {
+        let src = "var a = [1, 2, 3];\na[1] = \"two\";\npush(a, 4);\n\
+                   print a[1];\nprint len(a);\nprint pop(a);\nprint a;";
+        assert_eq!(run(src), "two\n4\n4\n[1, two, 3]\n");
+    }
+
+    #[test]
+    fn list_index_out_of_range_is_an_error() {
+        for src in ["var a = [1];\nprint a[1];", "var a = [1];\na[-1] = 2;"] {
+            let error = error(src);
+            assert_eq!(error.message, "List index out of range.");
+            assert_eq!(error.span.line, 2);
+        }
+        assert_eq!(
+            error("print [1][0.5];").message,
+            "List index must be an integer."
+        );
+        assert_eq!(error("pop([]);").message, "Can't pop from an empty list.");
+    }
+
+    #[test]
+    fn maps_can_be_built_read_and_updated() {
+        let src = "var m = {\"a\": 1, \"b\": 2, 3: \"three\"};\nm[\"a\"] = 10;\nm[\"c\"] = 4;\n\
+                   print m[\"a\"];\nprint m[3];\nprint len(m);";
+        assert_eq!(run(src), "10\nthree\n4\n");
+    }
+
+    #[test]
+    fn reading_a_missing_key_is_an_error_not_nil() {
+        let error = error("var m = {\"a\": nil};\nprint m[\"a\"];\nprint m[\"b\"];");
+        assert_eq!(error.message, "Key not found.");
+        assert_eq!(error.span.line, 3);
+    }
+
+    #[test]
+    fn only_strings_and_numbers_are_map_keys() {
+        let src = "var m = {nil: 1};";
+        let nil_key = error(src);
+        assert_eq!(nil_key.message, "Map keys must be strings or numbers.");
+        assert_eq!(nil_key.span.text(src), "nil");
+        assert_eq!(
+            error("var m = {};\nm[true] = 1;").message,
+            "Map keys must be strings or numbers."
+        );
+    }
+
+    #[test]
+    fn strings_are_measured_and_sliced_by_char() {
+        let src = "var s = \"héllo\";\nprint str_len(s) == 5;\nprint s[1];\n\
+                   print substring(s, 1, 3);\nprint substring(s, 0, 5) == s;";
+        assert_eq!(run(src), "true\né\néll\ntrue\n");
+    }
+
+    #[test]
+    fn out_of_range_string_access_is_an_error() {
+        assert_eq!(
+            error("print \"héllo\"[5];").message,
+            "String index out of range."
+        );
+        assert_eq!(
+            error("print substring(\"héllo\", 3, 3);").message,
+            "Substring out of range."
+        );
+        assert_eq!(
+            error("var s = \"a\";\ns[0] = \"b\";").message,
+            "Strings are immutable."
+        );
+    }
+
+    // runs src with integer literals scanned as Int, returning what it printed
+    fn run_ints(src: &str) -> Result<String, RuntimeError> {
+        let config = crate::ScannerConfig {
+            integers: true,
+            ..Default::default()
+        };
+        let tokens = crate::Scanner::with_config(src, config).scan().unwrap();
+        let stmts = crate::Parser::new(tokens).parse_program().unwrap();
+        let buf = crate::SharedBuf::default();
+        let mut interpreter = Interpreter::new_with_writer(buf.clone());
+        interpreter.resolve(crate::Resolver::new().resolve(&stmts).unwrap());
+        interpreter.interpret(&stmts)?;
+        let out = buf.0.take();
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn integers_stay_exact_above_two_to_the_53() {
+        let src = "var n = 9007199254740993; print n; print n + 2; print n == 9007199254740992;";
+        assert_eq!(
+            run_ints(src).unwrap(),
+            "9007199254740993\n9007199254740995\nfalse\n"
+        );
+        // without integer mode the literal is rounded to the nearest float
+        assert_eq!(run("print 9007199254740993 == 9007199254740992;"), "true\n");
+    }
+
+    #[test]
+    fn integers_promote_to_float_when_mixed() {
+        assert_eq!(
+            run_ints("print 7 / 2; print 1 + 0.5; print 2 ** -1; print 3 == 3.0;").unwrap(),
+            "3\n1.5\n0.5\ntrue\n"
+        );
+        let overflow = run_ints("print 9223372036854775807 + 1;").unwrap_err();
+        assert_eq!(overflow.message, "Integer overflow.");
+    }
+
+    #[test]
+    fn bitwise_operators_bind_tighter_than_equality() {
+        let src = "print 6 & 3 == 2; print 1 << 4 == 16;\n\
+                   print 5 | 2; print 6 ^ 3; print ~5; print -16 >> 2;";
+        assert_eq!(run(src), "true\ntrue\n7\n5\n-6\n-4\n");
+        assert_eq!(run_ints("print 6 & 3; print 1 << 4;").unwrap(), "2\n16\n");
+    }
+
+    #[test]
+    fn bitwise_operands_must_be_integers() {
+        assert_eq!(
+            error("print 1.5 & 1;").message,
+            "Operands must be integers."
+        );
+        assert_eq!(
+            error("print 1 | \"a\";").message,
+            "Operands must be integers."
+        );
+        assert_eq!(error("print ~0.5;").message, "Operand must be an integer.");
+        assert_eq!(
+            error("print 1 << 64;").message,
+            "Shift amount out of range."
+        );
+    }
+
+    #[test]
+    fn interpolated_strings_concatenate_their_parts() {
+        assert_eq!(run(r#"print "${1+2}!";"#), "3!\n");
+        assert_eq!(
+            run(r#"var n = "x"; print "a ${n} b ${ {"k": 2}["k"] * 2 } c";"#),
+            "a x b 4 c\n"
+        );
+        assert_eq!(run(r#"print "nested ${"in ${1}"}";"#), "nested in 1\n");
+        assert_eq!(run(r#"print "\${1+2}";"#), "${1+2}\n");
+    }
+
+    #[test]
+    fn print_goes_to_the_injected_writer() {
+        let buf = crate::SharedBuf::default();
+        let mut interpreter = Interpreter::new_with_writer(buf.clone());
+        crate::run_with(&mut interpreter, "print 1; print \"two\";").unwrap();
+        assert_eq!(&*buf.0.borrow(), b"1\ntwo\n");
+    }
+
+    // a sink that rejects every write, like a closed pipe
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failing_writer_is_a_runtime_error() {
+        let mut interpreter = Interpreter::new_with_writer(FailingWriter);
+        let result = crate::run_with(&mut interpreter, "var a = 1;\nprint a;");
+        let Err(LoxError::Runtime(write_error)) = result else {
+            panic!("expected a runtime error, got {:?}", result);
+        };
+        assert_eq!(write_error.span.line, 2);
+        assert!(write_error.message.starts_with("Can't write output: "));
+        let mut interpreter =
+            Interpreter::new_with_writer(io::sink()).with_trace_writer(FailingWriter);
+        let result = crate::run_with(&mut interpreter, "print 1;");
+        let Err(LoxError::Runtime(trace_error)) = result else {
+            panic!("expected a runtime error, got {:?}", result);
+        };
+        assert!(trace_error.message.starts_with("Can't write trace: "));
+    }
+
+    #[test]
+    fn passing_assertions_return_nil() {
+        let src =
+            "print assert(1 < 2); print assertEq(\"a\" + \"b\", \"ab\"); print assertEq(4, 2 * 2);";
+        assert_eq!(run(src), "nil\nnil\nnil\n");
+    }
+
+    #[test]
+    fn failing_assertions_describe_the_values() {
+        let unequal = error("assertEq(1, 2);");
+        assert_eq!(unequal.message, "Assertion failed: 1 != 2.");
+        assert_eq!(
+            error("assertEq(\"1\", 1);").message,
+            "Assertion failed: \"1\" != 1."
+        );
+        assert_eq!(
+            error("assert(nil);").message,
+            "Assertion failed: nil is not truthy."
+        );
+    }
+
+    #[test]
+    fn type_names_every_kind_of_value() {
+        let src = "class A {} fun f() {}\n\
+                   var values = [1.5, \"s\", true, nil, f, clock, fun () {}, A, A(), [], {}];\n\
+                   for (var i = 0; i < len(values); i++) print type(values[i]);";
+        assert_eq!(
+            run(src),
+            "number\nstring\nbool\nnil\nfunction\nfunction\nfunction\nclass\ninstance\nlist\nmap\n"
+        );
+        assert_eq!(run_ints("print type(1);").unwrap(), "number\n");
+    }
+
+    #[test]
+    fn numbers_and_strings_convert_both_ways() {
+        let src = "print to_string(3.5) + \"!\"; print to_number(to_string(3.5)) == 3.5;\n\
+                   print to_number(\" -2e1 \"); print to_number(\"abc\");\n\
+                   print to_number(\"1.\"); print to_string(true) + to_string(nil);";
+        assert_eq!(run(src), "3.5!\ntrue\n-20\nnil\nnil\ntruenil\n");
+    }
+
+    #[test]
+    fn restoring_a_snapshot_rolls_back_globals() {
+        let buf = crate::SharedBuf::default();
+        let mut interpreter = Interpreter::new_with_writer(buf.clone());
+        crate::run_with(&mut interpreter, "var x = 1; var l = [1]; var alias = l;").unwrap();
+        let snapshot = interpreter.globals().borrow().snapshot();
+        crate::run_with(&mut interpreter, "x = 2; push(l, 2); var y = 3;").unwrap();
+        interpreter.globals().borrow_mut().restore(snapshot);
+        let x = interpreter
+            .globals()
+            .borrow()
+            .get("x", Span::default())
+            .unwrap();
+        assert_eq!(x, Value::Number(1.0));
+        assert!(interpreter
+            .globals()
+            .borrow()
+            .get("y", Span::default())
+            .is_err());
+        // the restored list is still shared by both names
+        crate::run_with(&mut interpreter, "push(alias, 4); print l;").unwrap();
+        assert_eq!(&*buf.0.borrow(), b"[1, 4]\n");
+    }
+
+    // the default depth limit leaves room for an unoptimized build on a main thread's stack,
+    // which is bigger than a test thread's
+    fn with_main_stack(f: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn infinite_recursion_is_a_stack_overflow_error() {
+        with_main_stack(|| {
+            let overflow = error("fun f() { f(); }\nf();");
+            assert_eq!(overflow.message, "Stack overflow.");
+            assert_eq!(overflow.span.line, 1);
+        });
+        let mut interpreter = Interpreter::new_with_writer(io::sink()).with_max_depth(3);
+        let src = "fun down(n) { if (n > 0) down(n - 1); }";
+        crate::run_with(&mut interpreter, &format!("{} down(2);", src)).unwrap();
+        let result = crate::run_with(&mut interpreter, &format!("{} down(3);", src));
+        assert!(matches!(result, Err(LoxError::Runtime(e)) if e.message == "Stack overflow."));
+    }
+
+    fn trace(src: &str) -> String {
+        let buf = crate::SharedBuf::default();
+        let mut interpreter =
+            Interpreter::new_with_writer(io::sink()).with_trace_writer(buf.clone());
+        crate::run_with(&mut interpreter, src).unwrap();
+        let out = buf.0.take();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn trace_lists_each_statement_executed() {
+        let src = "var a = 1;\nif (a > 0) {\n  print a + 1;\n}\nwhile (a < 3) a = a + 1;";
+        assert_eq!(
+            trace(src),
+            "[line 1] var a => 1\n\
+             [line 2] if\n\
+             [line 2] block\n\
+             [line 3] print => 2\n\
+             [line 5] while\n\
+             [line 5] expression => 2\n\
+             [line 5] expression => 3\n"
+        );
+    }
+
+    #[test]
+    fn statements_traced_after_a_call_keep_their_own_line() {
+        let src = "fun f() {\n  return 4;\n}\nf();\nprint f();";
+        assert_eq!(
+            trace(src),
+            "[line 1] fun f\n\
+             [line 2] return => 4\n\
+             [line 4] expression => 4\n\
+             [line 2] return => 4\n\
+             [line 5] print => 4\n"
+        );
+    }
+
+    #[test]
+    fn running_out_of_gas_stops_an_infinite_loop() {
+        let mut interpreter = Interpreter::new_with_writer(io::sink()).with_gas(100);
+        let result = crate::run_with(&mut interpreter, "var n = 0;\nwhile (true) { n = n + 1; }");
+        let Err(LoxError::Runtime(budget)) = result else {
+            panic!("expected a runtime error, got {:?}", result);
+        };
+        assert_eq!(budget.message, "Budget exceeded.");
+        assert_eq!(budget.span.line, 2);
+        // each pass costs a fixed number of steps, so the loop ran a bounded number of times
+        let n = interpreter
+            .globals()
+            .borrow()
+            .get("n", Span::default())
+            .unwrap();
+        assert!(
+            matches!(n, Value::Number(n) if (10.0..100.0).contains(&n)),
+            "{:?}",
+            n
+        );
+        let mut interpreter = Interpreter::new_with_writer(io::sink()).with_gas(100);
+        assert!(crate::run_with(&mut interpreter, "var n = 1 + 2;").is_ok());
+    }
+
+    #[test]
+    fn power_is_right_associative_and_binds_tighter_than_unary() {
+        let src = "print 2 ** 10 == 1024; print 2 ** 3 ** 2 == 512;\n\
+                   print -2 ** 2; print 2 * 3 ** 2;";
+        assert_eq!(run(src), "true\ntrue\n-4\n18\n");
+        assert_eq!(
+            error("print 2 ** \"a\";").message,
+            "Operands must be numbers."
+        );
+    }
+
+    #[test]
+    fn read_line_reads_from_the_injected_input() {
+        let buf = crate::SharedBuf::default();
+        let input = io::Cursor::new("first line\r\nsecond\n");
+        let mut interpreter = Interpreter::new_with_writer(buf.clone()).with_input(input);
+        let src = "print \"> \" + read_line(); print read_line(); print read_line();";
+        crate::run_with(&mut interpreter, src).unwrap();
+        assert_eq!(&*buf.0.borrow(), b"> first line\nsecond\nnil\n");
+    }
+
+    #[test]
+    fn file_natives_need_file_access() {
+        assert_eq!(
+            error("read_file(\"x\");").message,
+            "File access is disabled."
+        );
+        assert_eq!(
+            error("write_file(\"x\", \"y\");").message,
+            "File access is disabled."
+        );
+    }
+
+    #[test]
+    fn written_file_reads_back() {
+        let path =
+            std::env::temp_dir().join(format!("lox-file-natives-{}.txt", std::process::id()));
+        // as a Lox string literal
+        let path = path.to_str().unwrap().replace('\\', "\\\\");
+        let buf = crate::SharedBuf::default();
+        let mut interpreter = Interpreter::new_with_writer(buf.clone()).with_file_access(true);
+        let src = format!(
+            "print write_file(\"{0}\", \"saved\"); print read_file(\"{0}\");",
+            path
+        );
+        crate::run_with(&mut interpreter, &src).unwrap();
+        assert_eq!(&*buf.0.borrow(), b"nil\nsaved\n");
+        std::fs::remove_file(path.replace("\\\\", "\\")).unwrap();
+        let result = crate::run_with(&mut interpreter, &format!("read_file(\"{}\");", path));
+        assert!(matches!(result, Err(LoxError::Runtime(e)) if e.message.starts_with("Can't read")));
+    }
+
+    #[test]
+    fn repr_escapes_strings_that_print_writes_raw() {
+        assert_eq!(run("print \"a\\nb\";"), "a\nb\n");
+        let s = "a\nb\t\"q\" \\ ${x} \u{1}";
+        let echoed = Value::Str(s.to_string()).repr().to_string();
+        assert_eq!(echoed, r#""a\nb\t\"q\" \\ \${x} \u{1}""#);
+        // the echoed form scans back to the same string
+        let tokens = crate::Scanner::new(&echoed).scan().unwrap();
+        assert_eq!(tokens[0].typ, crate::TokenType::Str(s.to_string()));
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Serialize, Serializer};
+
+use crate::{BinaryOp, Expr, LogicalOp, Span, UnaryOp, UpdateOp};
+
+// the JSON the serde feature gives syntax trees, as printed by --ast-json. every node is an
+// object with a "type", the name of its Expr or Stmt variant (e.g. "Binary" or "Var"), and the
+// "span" recorded on it, e.g. only the operator of a binary expression or the name in a var
+// declaration. the rest of its fields are the variant's, with operators as their source text,
+// literals as plain JSON values and then_branch and else_branch as "then" and "else". a Function
+// or Lambda node has the fields of its FunctionDecl, and map entries and parameters are
+// [key, value] and [name, span] pairs. the ids used by the resolver are left out
+
+// an expression statement spans its expression, without the semicolon
+pub(crate) fn expression_stmt<S: Serializer>(
+    expr: &Expr,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Expression<'a> {
+        span: Span,
+        expr: &'a Expr,
+    }
+    Expression {
+        span: expr.span(),
+        expr,
+    }
+    .serialize(serializer)
+}
+
+impl Serialize for UnaryOp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for UpdateOp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for LogicalOp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for BinaryOp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Scanner;
+
+    #[test]
+    fn tokens_serialize_with_their_type_and_value() {
+        let tokens = Scanner::new("1 + 2").scan().unwrap();
+        let text = serde_json::to_string(&tokens).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([
+                {
+                    "type": "Number",
+                    "value": 1.0,
+                    "lexeme": "1",
+                    "span": {"start": 0, "end": 1, "line": 1, "col": 1},
+                },
+                {
+                    "type": "Plus",
+                    "lexeme": "+",
+                    "span": {"start": 2, "end": 3, "line": 1, "col": 3},
+                },
+                {
+                    "type": "Number",
+                    "value": 2.0,
+                    "lexeme": "2",
+                    "span": {"start": 4, "end": 5, "line": 1, "col": 5},
+                },
+                {
+                    "type": "Eof",
+                    "lexeme": "",
+                    "span": {"start": 5, "end": 5, "line": 1, "col": 6},
+                },
+            ])
+        );
+    }
+
+    fn ast_json(src: &str) -> serde_json::Value {
+        let stmts = crate::Parser::new(Scanner::new(src).scan().unwrap())
+            .parse_program()
+            .unwrap();
+        serde_json::to_value(&stmts).unwrap()
+    }
+
+    #[test]
+    fn ast_json_of_a_var_declaration() {
+        // a span on the first line
+        fn span(start: usize, end: usize) -> serde_json::Value {
+            serde_json::json!({"start": start, "end": end, "line": 1, "col": start + 1})
+        }
+        assert_eq!(
+            ast_json("var x = 1 + 2;"),
+            serde_json::json!([{
+                "type": "Var",
+                "span": span(4, 5),
+                "name": "x",
+                "initializer": {
+                    "type": "Binary",
+                    "span": span(10, 11),
+                    "left": {"type": "Literal", "span": span(8, 9), "value": 1.0},
+                    "op": "+",
+                    "right": {"type": "Literal", "span": span(12, 13), "value": 2.0},
+                },
+            }])
+        );
+    }
+
+    // every node of every kind has a real span
+    #[test]
+    fn ast_json_spans_are_never_null() {
+        fn check(value: &serde_json::Value) {
+            match value {
+                serde_json::Value::Object(fields) => {
+                    if fields.contains_key("type") {
+                        assert!(fields["span"].is_object(), "{}", value);
+                    }
+                    fields.values().for_each(check);
+                }
+                serde_json::Value::Array(values) => values.iter().for_each(check),
+                _ => {}
+            }
+        }
+        let src = "class A < B { m() { return super.m(this); } }\n\
+                   fun f(a) { { print a ? [1] : {\"k\": nil}; } }\n\
+                   for (var i = 0; i < 3; i++) { if (i == 1) continue; else break; }\n\
+                   while (false) f(fun (x) { x += 1; return; })[0] = -(1, 2);";
+        check(&ast_json(src));
+    }
+
+    // an expression statement wraps its expression, so neither node's "type" is lost, and a
+    // function node has the fields of its declaration
+    #[test]
+    fn ast_json_of_an_expression_statement_and_a_lambda() {
+        let stmts = ast_json("f(fun (a) {});");
+        assert_eq!(stmts[0]["type"], "Expression");
+        assert_eq!(stmts[0]["span"]["end"], 13);
+        let call = &stmts[0]["expr"];
+        assert_eq!(call["type"], "Call");
+        assert_eq!(
+            call["args"][0],
+            serde_json::json!({
+                "type": "Lambda",
+                "name": "",
+                "span": {"start": 2, "end": 5, "line": 1, "col": 3},
+                "params": [["a", {"start": 7, "end": 8, "line": 1, "col": 8}]],
+                "body": [],
+            })
+        );
+    }
+}
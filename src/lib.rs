@@ -0,0 +1,180 @@
+mod chunk;
+mod compiler;
+mod diagnostic;
+mod interner;
+mod interpreter;
+#[cfg(feature = "serde")]
+mod json;
+mod line_index;
+mod optimizer;
+mod parser;
+mod resolver;
+mod scanner;
+mod span;
+mod visitor;
+mod vm;
+
+use std::{cell::RefCell, fmt::Display, io::Write, rc::Rc};
+
+pub use chunk::{disassemble, disassemble_instruction, Chunk, OpCode};
+pub use compiler::{compile_to_chunk, CompileError};
+pub use diagnostic::{render_diagnostic, render_diagnostic_with_tab_width};
+pub use interner::{Interner, Symbol};
+pub use interpreter::{
+    Class, EnvSnapshot, Environment, Function, Instance, Interpreter, Map, MapKey, Native,
+    NativeFn, Repr, RuntimeError, Value,
+};
+pub use line_index::LineIndex;
+pub use optimizer::{optimize, optimize_stmts};
+pub use parser::{
+    BinaryOp, Expr, ExprId, FunctionDecl, Literal, LogicalOp, ParseError, Parser, Stmt, UnaryOp,
+    UpdateOp,
+};
+pub use resolver::{ResolveError, Resolver, UnusedLint, Warning};
+pub use scanner::{
+    tokens_to_source, ScanError, ScanResult, Scanner, ScannerConfig, Token, TokenType,
+};
+pub use span::Span;
+pub use visitor::{ExprVisitor, StmtVisitor};
+pub use vm::VM;
+
+// any error that stops a program, from whichever stage reported it
+#[derive(Debug)]
+pub enum LoxError {
+    Scan(Vec<ScanError>),
+    Parse(Vec<ParseError>),
+    Resolve(Vec<ResolveError>),
+    Runtime(RuntimeError),
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn lines(f: &mut std::fmt::Formatter<'_>, errors: &[impl Display]) -> std::fmt::Result {
+            for (i, error) in errors.iter().enumerate() {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                write!(f, "{}", error)?;
+            }
+            Ok(())
+        }
+        match self {
+            LoxError::Scan(errors) => lines(f, errors),
+            LoxError::Parse(errors) => lines(f, errors),
+            LoxError::Resolve(errors) => lines(f, errors),
+            LoxError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+// runs a whole program, printing to stdout
+pub fn run(src: &str) -> Result<(), LoxError> {
+    run_with(&mut Interpreter::new(), src)
+}
+
+// runs a whole program, returning what it printed
+pub fn run_to_string(src: &str) -> Result<String, LoxError> {
+    let (out, result) = run_captured(src);
+    result.map(|()| out)
+}
+
+// everything a program printed followed by the error that stopped it, if any, for hosts like a
+// browser playground that have no stdout, stderr or stdin
+#[cfg(feature = "wasm")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn run_wasm(src: &str) -> String {
+    let (mut out, result) = run_captured(src);
+    if let Err(error) = result {
+        out.push_str(&format!("{}\n", error));
+    }
+    out
+}
+
+// runs a program, keeping what it printed before any error. read_line sees no input rather
+// than reading stdin
+fn run_captured(src: &str) -> (String, Result<(), LoxError>) {
+    let buf = SharedBuf::default();
+    let mut interpreter = Interpreter::new_with_writer(buf.clone()).with_input(std::io::empty());
+    let result = run_with(&mut interpreter, src);
+    let out = buf.0.take();
+    (String::from_utf8_lossy(&out).into_owned(), result)
+}
+
+// runs a whole program on interpreter, which is parsed the way interpreter was configured to
+pub fn run_with(interpreter: &mut Interpreter, src: &str) -> Result<(), LoxError> {
+    let tokens = Scanner::new(src).scan().map_err(LoxError::Scan)?;
+    let stmts = Parser::new(tokens)
+        .with_strict_declarations(interpreter.strict_declarations())
+        .parse_program()
+        .map_err(LoxError::Parse)?;
+    let locals = Resolver::new().resolve(&stmts).map_err(LoxError::Resolve)?;
+    interpreter.resolve(locals);
+    interpreter.interpret(&stmts).map_err(LoxError::Runtime)
+}
+
+// a writer whose contents can still be read after it's been handed to the interpreter
+#[derive(Clone, Default)]
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_to_string_returns_what_was_printed() {
+        assert_eq!(run_to_string("print 1+2;").unwrap(), "3\n");
+        assert!(matches!(
+            run_to_string("print x;"),
+            Err(LoxError::Runtime(_))
+        ));
+        assert!(matches!(run_to_string("print 1"), Err(LoxError::Parse(_))));
+    }
+
+    #[test]
+    fn run_captured_keeps_output_from_before_an_error() {
+        let (out, result) = run_captured("print 1;\nprint read_line();\nprint -nil;");
+        assert_eq!(out, "1\nnil\n");
+        let Err(LoxError::Runtime(error)) = result else {
+            panic!("expected a runtime error, got {:?}", result);
+        };
+        assert_eq!(
+            error.to_string(),
+            "[line 3] Error: Operand must be a number."
+        );
+    }
+
+    #[test]
+    fn run_with_parses_with_the_interpreter_strict_declarations() {
+        let src = "var a;\nprint a;";
+        let buf = SharedBuf::default();
+        run_with(&mut Interpreter::new_with_writer(buf.clone()), src).unwrap();
+        assert_eq!(&*buf.0.borrow(), b"nil\n");
+        let mut strict = Interpreter::new_with_writer(buf).with_strict_declarations(true);
+        let Err(LoxError::Parse(errors)) = run_with(&mut strict, src) else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(errors[0].message, "Variable must be initialized.");
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn run_wasm_appends_the_error_to_the_output() {
+        assert_eq!(
+            run_wasm("print 1;\nprint -nil;"),
+            "1\n[line 2] Error: Operand must be a number.\n"
+        );
+    }
+}
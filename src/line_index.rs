@@ -0,0 +1,93 @@
+use std::ops::Range;
+
+// where each line of a source starts, for turning byte offsets back into lines and columns. line
+// breaks are the scanner's: \n, \r\n or a lone \r
+#[derive(Debug)]
+pub struct LineIndex<'src> {
+    src: &'src str,
+    line_starts: Vec<usize>,
+}
+
+impl<'src> LineIndex<'src> {
+    pub fn new(src: &str) -> LineIndex<'_> {
+        let bytes = src.as_bytes();
+        // columns on the first line start after a byte-order mark, as in the scanner
+        let mut line_starts = vec![if src.starts_with('\u{feff}') { 3 } else { 0 }];
+        for (i, &b) in bytes.iter().enumerate() {
+            if b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n')) {
+                line_starts.push(i + 1);
+            }
+        }
+        LineIndex { src, line_starts }
+    }
+
+    // 1-based, with columns counted in chars; offsets past the end are clamped to it
+    pub fn offset_to_line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.src.len());
+        let line = self
+            .line_starts
+            .partition_point(|&start| start <= offset)
+            .max(1);
+        let start = self.line_starts[line - 1].min(offset);
+        // utf-8 continuation bytes don't start a new char
+        let col = self.src.as_bytes()[start..offset]
+            .iter()
+            .filter(|&&b| b & 0xc0 != 0x80)
+            .count();
+        (line, col + 1)
+    }
+
+    // the byte range of a 1-based line, without its line break
+    pub fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[line - 1];
+        let end = match self.line_starts.get(line) {
+            Some(&next) if self.src[..next].ends_with("\r\n") => next - 2,
+            Some(&next) => next - 1,
+            None => self.src.len(),
+        };
+        start..end
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_map_to_lines_and_columns() {
+        let index = LineIndex::new("ab\ncd\r\nef\rg");
+        assert_eq!(index.line_count(), 4);
+        let positions: Vec<_> = [0, 2, 3, 6, 7, 10, 11, 100]
+            .into_iter()
+            .map(|offset| index.offset_to_line_col(offset))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                (1, 1),
+                (1, 3),
+                (2, 1),
+                (2, 4),
+                (3, 1),
+                (4, 1),
+                (4, 2),
+                (4, 2)
+            ]
+        );
+        let ranges: Vec<_> = (1..=4).map(|line| index.line_range(line)).collect();
+        assert_eq!(ranges, [0..2, 3..5, 7..9, 10..11]);
+    }
+
+    #[test]
+    fn eof_after_a_trailing_newline_is_on_a_new_line() {
+        let index = LineIndex::new("a\n");
+        assert_eq!(index.offset_to_line_col(2), (2, 1));
+        assert_eq!(index.line_range(2), 2..2);
+        // columns count chars rather than bytes
+        assert_eq!(LineIndex::new("é x").offset_to_line_col(3), (1, 3));
+    }
+}
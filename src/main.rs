@@ -1,299 +1,398 @@
 use std::{
+    collections::HashMap,
     env,
     fmt::Display,
     fs,
-    io::{self, Write},
+    io::{self, BufRead, IsTerminal, Read, Write},
     process,
+    time::Instant,
 };
 
-fn main() -> Result<(), io::Error> {
-    let args: Vec<_> = env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        process::exit(64);
-    }
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    match args.get(1) {
-        Some(f) => {
-            let prog = fs::read_to_string(f)?;
-            run(&prog);
-        }
-        None => {
-            let mut line = String::new();
-            loop {
-                write!(&mut stdout, "> ")?;
-                stdout.flush()?;
-                let n = stdin.read_line(&mut line)?;
-                if n == 0 {
-                    break;
-                }
-                run(&line);
-                line.clear();
-            }
-        }
-    }
-    Ok(())
+use lox::{
+    compile_to_chunk, disassemble, optimize, optimize_stmts, render_diagnostic_with_tab_width,
+    Chunk, Expr, ExprId, Interpreter, Parser, Resolver, Scanner, ScannerConfig, Span, Stmt, Token,
+    UnusedLint, VM,
+};
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Run,
+    Tokens,
+    Ast,
+    #[cfg(feature = "serde")]
+    Json,
+    #[cfg(feature = "serde")]
+    AstJson,
+    Disasm,
+    Vm,
+    Check,
 }
 
-fn run(src: &str) {
-    let mut scanner = Scanner::new(src);
-    let tokens = scanner.scan();
-    dbg!(tokens);
+// settings from flags that apply whatever the mode
+#[derive(Default)]
+struct Options {
+    scanner: ScannerConfig,
+    strict_declarations: bool,
+    optimize: bool,
+    // print how long each phase took
+    time: bool,
+    // let scripts use read_file and write_file
+    allow_files: bool,
+    unused: UnusedLint,
 }
 
-struct Scanner<'src> {
-    src: &'src str,
-    start: usize,
-    curr: usize,
-    line: usize,
-    errors: Vec<ScanError>,
+// what kind of failure stopped the program; the errors themselves are already reported
+enum RunError {
+    Compile,
+    Runtime,
 }
 
-impl<'src> Scanner<'src> {
-    fn new(src: &str) -> Scanner {
-        Scanner {
-            src,
-            start: 0,
-            curr: 0,
-            line: 1,
-            errors: vec![],
+fn main() -> Result<(), io::Error> {
+    let mut mode = Mode::Run;
+    let mut options = Options::default();
+    let mut script = None;
+    for arg in env::args().skip(1) {
+        if let Some(width) = tab_width(&arg) {
+            options.scanner.tab_width = width;
+            continue;
+        }
+        match arg.as_str() {
+            "--tokens" => mode = Mode::Tokens,
+            "--ast" => mode = Mode::Ast,
+            #[cfg(feature = "serde")]
+            "--emit-json" => mode = Mode::Json,
+            #[cfg(feature = "serde")]
+            "--ast-json" => mode = Mode::AstJson,
+            "--disasm" => mode = Mode::Disasm,
+            "--vm" => mode = Mode::Vm,
+            "--check" => mode = Mode::Check,
+            "--int" => options.scanner.integers = true,
+            "--strict-declarations" => options.strict_declarations = true,
+            "--opt" => options.optimize = true,
+            "--time" => options.time = true,
+            "--allow-files" => options.allow_files = true,
+            "--warn-unused" => options.unused = UnusedLint::Locals,
+            "--warn-unused-all" => options.unused = UnusedLint::All,
+            _ if script.is_none() && !arg.starts_with("--") => script = Some(arg),
+            _ => {
+                println!(
+                    "Usage: rlox [--tokens | --ast | --emit-json | --ast-json | --disasm | --vm \
+                     | --check] [--int] [--strict-declarations] [--opt] [--time] [--allow-files] \
+                     [--warn-unused | --warn-unused-all] [--tab-width=N] [script | -]"
+                );
+                process::exit(64);
+            }
         }
     }
-
-    fn scan(&mut self) -> Vec<Token<'_>> {
-        let mut tokens = vec![];
-        while let Some(b) = self.advance() {
-            match b {
-                b'(' => tokens.push(self.make_token(TokenType::LParen)),
-                b')' => tokens.push(self.make_token(TokenType::RParen)),
-                b'{' => tokens.push(self.make_token(TokenType::LBrace)),
-                b'}' => tokens.push(self.make_token(TokenType::RBrace)),
-                b',' => tokens.push(self.make_token(TokenType::Comma)),
-                b'.' => tokens.push(self.make_token(TokenType::Dot)),
-                b'-' => tokens.push(self.make_token(TokenType::Minus)),
-                b'+' => tokens.push(self.make_token(TokenType::Plus)),
-                b';' => tokens.push(self.make_token(TokenType::Semicolon)),
-                b'*' => tokens.push(self.make_token(TokenType::Star)),
-                b'!' => {
-                    let typ = if self.advance_if_match(b'=') {
-                        TokenType::BangEqual
-                    } else {
-                        TokenType::Bang
-                    };
-                    tokens.push(self.make_token(typ))
-                }
-                b'=' => {
-                    let typ = if self.advance_if_match(b'=') {
-                        TokenType::EqualEqual
-                    } else {
-                        TokenType::Equal
-                    };
-                    tokens.push(self.make_token(typ))
-                }
-                b'<' => {
-                    let typ = if self.advance_if_match(b'=') {
-                        TokenType::LessEqual
-                    } else {
-                        TokenType::Less
-                    };
-                    tokens.push(self.make_token(typ))
-                }
-                b'>' => {
-                    let typ = if self.advance_if_match(b'=') {
-                        TokenType::GreaterEqual
-                    } else {
-                        TokenType::Greater
-                    };
-                    tokens.push(self.make_token(typ))
-                }
-                b'/' => {
-                    // is comment
-                    if self.advance_if_match(b'/') {
-                        loop {
-                            match self.peek() {
-                                Some(c) if c != b'\n' => {}
-                                _ => break,
-                            }
-                        }
-                    } else {
-                        tokens.push(self.make_token(TokenType::Slash));
-                    }
-                }
-                b' ' | b'\r' | b'\t' => {}
-                b'\n' => {
-                    self.line += 1;
-                }
-                b'"' => {
-                    if let Some(token) = self.scan_str() {
-                        tokens.push(token);
-                    }
-                }
-                b if b.is_ascii_digit() => {
-                    if let Some(token) = self.scan_num() {
-                        tokens.push(token);
-                    }
-                }
-                _ => {
-                    self.errors.push(ScanError {
-                        line: self.line,
-                        message: "Unexpected character.".to_string(),
-                    });
-                }
+    let stdin = io::stdin();
+    let mut interpreter = Interpreter::new().with_file_access(options.allow_files);
+    // piped input is a whole program rather than a REPL session
+    let script = match script {
+        None if !stdin.is_terminal() => Some("-".to_string()),
+        script => script,
+    };
+    match script {
+        Some(f) => {
+            let prog = if f == "-" {
+                let mut prog = String::new();
+                stdin.lock().read_to_string(&mut prog)?;
+                prog
+            } else {
+                fs::read_to_string(f)?
+            };
+            match run(&mut interpreter, &prog, mode, &options) {
+                Ok(()) => {}
+                Err(RunError::Compile) => process::exit(65),
+                Err(RunError::Runtime) => process::exit(70),
             }
-
-            self.start = self.curr;
         }
-        tokens.push(self.make_token(TokenType::Eof));
-        tokens
+        None => repl(&mut interpreter, &mut stdin.lock(), mode, &options)?,
     }
+    Ok(())
+}
 
-    fn scan_str<'a>(&'a mut self) -> Option<Token<'src>> {
-        loop {
-            match self.advance() {
-                None => {
-                    self.errors.push(ScanError {
-                        line: self.line,
-                        message: "Unterminated string.".to_string(),
-                    });
-                    break None;
-                }
-                Some(b'\n') => {
-                    self.line += 1;
-                }
-                Some(b'"') => {
-                    break Some(
-                        self.make_token(TokenType::Str(&self.src[self.start + 1..self.curr - 1])),
-                    );
-                }
-                _ => {}
-            }
+// reads lines from input until it ends, running each complete one with the same interpreter so
+// definitions carry over
+fn repl(
+    interpreter: &mut Interpreter,
+    input: &mut impl BufRead,
+    mode: Mode,
+    options: &Options,
+) -> Result<(), io::Error> {
+    let mut stdout = io::stdout();
+    let mut src = String::new();
+    loop {
+        let prompt = if src.is_empty() { "> " } else { "... " };
+        write!(&mut stdout, "{}", prompt)?;
+        stdout.flush()?;
+        let n = input.read_line(&mut src)?;
+        if n == 0 {
+            break;
         }
+        // a blank line submits whatever has been entered so far
+        let blank = src[src.len() - n..].trim().is_empty();
+        let expr = parse_expression(&src, &options.scanner);
+        if expr.is_none() && !blank && is_incomplete(&src, &options.scanner) {
+            continue;
+        }
+        // errors are reported but don't end the session
+        let _ = match (mode, expr) {
+            (Mode::Run, Some(expr)) => echo(interpreter, &src, expr, options),
+            _ => run(interpreter, &src, mode, options),
+        };
+        src.clear();
     }
+    Ok(())
+}
 
-    fn scan_num<'a>(&'a mut self) -> Option<Token<'src>> {
-        loop {
-            match self.peek() {
-                Some(c) if c.is_ascii_digit() => {
-                    self.advance();
+fn run(
+    interpreter: &mut Interpreter,
+    src: &str,
+    mode: Mode,
+    options: &Options,
+) -> Result<(), RunError> {
+    let tokens = match timed(options, "scan", || {
+        Scanner::with_config(src, options.scanner.clone()).scan()
+    }) {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            for error in &errors {
+                match mode {
+                    #[cfg(feature = "serde")]
+                    Mode::Json => println!("{}", to_json(error)),
+                    _ => report(options, src, error.span, error),
                 }
-                _ => break,
             }
+            return Err(RunError::Compile);
         }
-        if self.peek() == Some(b'.') && self.peek_next().map_or(false, |b| b.is_ascii_digit()) {
-            self.advance();
-            loop {
-                match self.peek() {
-                    Some(c) if c.is_ascii_digit() => {
-                        self.advance();
-                    }
-                    _ => break,
-                }
+    };
+    match mode {
+        Mode::Tokens => {
+            for token in tokens {
+                println!("{}\t{}\t{:?}", token.span, token.typ, token.lexeme);
             }
         }
-        Some(self.make_token(TokenType::Number(
-            self.src[self.start..self.curr].parse().ok()?,
-        )))
-    }
-
-    fn peek(&self) -> Option<u8> {
-        self.src.bytes().nth(self.curr)
+        #[cfg(feature = "serde")]
+        Mode::Json => println!("{}", to_json(&tokens)),
+        Mode::Ast => println!("{}", parse_expr(src, tokens, options)?),
+        // the whole program, unlike --ast, in the schema described in json.rs
+        #[cfg(feature = "serde")]
+        Mode::AstJson => {
+            let stmts = optimized(parse_program(src, tokens, options)?, options);
+            println!("{}", to_json(&stmts));
+        }
+        // only single expressions can be compiled so far
+        Mode::Disasm => {
+            let expr = parse_expr(src, tokens, options)?;
+            print!("{}", disassemble(&compile(src, &expr, options)?, "code"));
+        }
+        Mode::Vm => {
+            let expr = parse_expr(src, tokens, options)?;
+            let chunk = compile(src, &expr, options)?;
+            let value = timed(options, "interpret", || VM::new(chunk).run()).map_err(|error| {
+                report(options, src, error.span, &error);
+                RunError::Runtime
+            })?;
+            println!("{}", value);
+        }
+        Mode::Run => {
+            let stmts = parse_program(src, tokens, options)?;
+            execute(interpreter, src, stmts, options)?;
+        }
+        // reports every static error without running anything
+        Mode::Check => {
+            let stmts = parse_program(src, tokens, options)?;
+            timed(options, "resolve", || resolve(src, &stmts, options))?;
+        }
     }
+    Ok(())
+}
 
-    fn peek_next(&self) -> Option<u8> {
-        self.src.bytes().nth(self.curr + 1)
-    }
+fn parse_expr(src: &str, tokens: Vec<Token>, options: &Options) -> Result<Expr, RunError> {
+    let expr = timed(options, "parse", || Parser::new(tokens).parse()).map_err(|error| {
+        report(options, src, error.span, &error);
+        RunError::Compile
+    })?;
+    Ok(if options.optimize {
+        optimize(expr)
+    } else {
+        expr
+    })
+}
 
-    fn advance(&mut self) -> Option<u8> {
-        let b = self.src.bytes().nth(self.curr)?;
-        self.curr += 1;
-        Some(b)
-    }
+fn compile(src: &str, expr: &Expr, options: &Options) -> Result<Chunk, RunError> {
+    compile_to_chunk(expr).map_err(|error| {
+        report(options, src, error.span, &error);
+        RunError::Compile
+    })
+}
 
-    fn advance_if_match(&mut self, expected: u8) -> bool {
-        if self.peek() == Some(expected) {
-            self.curr += 1;
-            true
-        } else {
-            false
+fn parse_program(src: &str, tokens: Vec<Token>, options: &Options) -> Result<Vec<Stmt>, RunError> {
+    let stmts = timed(options, "parse", || {
+        Parser::new(tokens)
+            .with_strict_declarations(options.strict_declarations)
+            .parse_program()
+    })
+    .map_err(|errors| {
+        for error in &errors {
+            report(options, src, error.span, error);
         }
+        RunError::Compile
+    })?;
+    Ok(stmts)
+}
+
+// folds constants if --opt was given. this comes after resolving, so the resolver's lints see
+// the program as written, e.g. 1 < 2 < 3 rather than the folded true < 3
+fn optimized(stmts: Vec<Stmt>, options: &Options) -> Vec<Stmt> {
+    if options.optimize {
+        optimize_stmts(stmts)
+    } else {
+        stmts
     }
+}
 
-    fn make_token<'a>(&'a self, typ: TokenType<'src>) -> Token<'src> {
-        Token {
-            typ,
-            lexeme: &self.src[self.start..self.curr],
-            line: self.line,
-        }
+fn resolve(
+    src: &str,
+    stmts: &[Stmt],
+    options: &Options,
+) -> Result<HashMap<ExprId, usize>, RunError> {
+    let (result, warnings) = Resolver::new()
+        .with_unused_lint(options.unused)
+        .resolve_with_warnings(stmts);
+    for warning in &warnings {
+        report(options, src, warning.span, warning);
     }
+    result.map_err(|errors| {
+        for error in &errors {
+            report(options, src, error.span, error);
+        }
+        RunError::Compile
+    })
 }
 
-#[derive(Debug)]
-struct Token<'src> {
-    typ: TokenType<'src>,
-    lexeme: &'src str,
-    line: usize,
+fn execute(
+    interpreter: &mut Interpreter,
+    src: &str,
+    stmts: Vec<Stmt>,
+    options: &Options,
+) -> Result<(), RunError> {
+    let locals = timed(options, "resolve", || resolve(src, &stmts, options))?;
+    interpreter.resolve(locals);
+    // folding keeps the ids of the expressions it doesn't replace, so locals still apply
+    let stmts = optimized(stmts, options);
+    timed(options, "interpret", || interpreter.interpret(&stmts)).map_err(|error| {
+        report(options, src, error.span, &error);
+        RunError::Runtime
+    })
 }
 
-#[derive(Debug)]
-enum TokenType<'src> {
-    // Single-character tokens.
-    LParen,
-    RParen,
-    LBrace,
-    RBrace,
-    Comma,
-    Dot,
-    Minus,
-    Plus,
-    Semicolon,
-    Slash,
-    Star,
+// prints the value of a REPL expression as source, so strings are quoted and escaped
+fn echo(
+    interpreter: &mut Interpreter,
+    src: &str,
+    expr: Expr,
+    options: &Options,
+) -> Result<(), RunError> {
+    let stmts = [Stmt::Expression(expr)];
+    let locals = timed(options, "resolve", || resolve(src, &stmts, options))?;
+    interpreter.resolve(locals);
+    let [Stmt::Expression(expr)] = stmts else {
+        unreachable!("stmts is the expression statement built above");
+    };
+    let expr = if options.optimize {
+        optimize(expr)
+    } else {
+        expr
+    };
+    let value = timed(options, "interpret", || interpreter.eval(&expr)).map_err(|error| {
+        report(options, src, error.span, &error);
+        RunError::Runtime
+    })?;
+    println!("{}", value.repr());
+    Ok(())
+}
+
+// --emit-json and --ast-json output, in the shape serde derives for tokens, scan errors and trees
+#[cfg(feature = "serde")]
+fn to_json(value: &impl serde::Serialize) -> String {
+    serde_json::to_string(value).expect("tokens, errors and trees always serialize")
+}
 
-    // One or two character tokens.
-    Bang,
-    BangEqual,
-    Equal,
-    EqualEqual,
-    Greater,
-    GreaterEqual,
-    Less,
-    LessEqual,
+// runs phase, printing e.g. parse=0.125 (in milliseconds) to stderr if --time was given
+fn timed<T>(options: &Options, phase: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    if options.time {
+        eprintln!("{}={:.3}", phase, start.elapsed().as_secs_f64() * 1000.0);
+    }
+    result
+}
 
-    // Literals.
-    Ident,
-    Str(&'src str),
-    Number(f64),
+// the width of a --tab-width=N flag, which must be at least 1
+fn tab_width(arg: &str) -> Option<usize> {
+    let width = arg.strip_prefix("--tab-width=")?.parse().ok()?;
+    (width > 0).then_some(width)
+}
 
-    // Keywords.
-    And,
-    Class,
-    Else,
-    False,
-    Fun,
-    For,
-    If,
-    Nil,
-    Or,
-    Print,
-    Return,
-    Super,
-    This,
-    True,
-    Var,
-    While,
+// prints error with the source it points at underlined. tabs are expanded to the same stops
+// the scanner counted columns with, so the caret lines up with the column in the header
+fn report(options: &Options, src: &str, span: Span, error: impl Display) {
+    let tab_width = options.scanner.tab_width;
+    eprintln!(
+        "{}",
+        render_diagnostic_with_tab_width(src, span, &error.to_string(), tab_width)
+    );
+}
 
-    Eof,
+// a REPL line that is a lone expression has its value printed
+fn parse_expression(src: &str, config: &ScannerConfig) -> Option<Expr> {
+    let tokens = Scanner::with_config(src, config.clone()).scan().ok()?;
+    Parser::new(tokens).parse().ok()
 }
 
-#[derive(Debug)]
-struct ScanError {
-    line: usize,
-    message: String,
+// whether src stops partway through a construct, so the REPL should read another line
+fn is_incomplete(src: &str, config: &ScannerConfig) -> bool {
+    match Scanner::with_config(src, config.clone()).scan() {
+        Err(errors) => errors
+            .iter()
+            .any(|error| error.message.starts_with("Unterminated")),
+        Ok(tokens) => match Parser::new(tokens).parse_program() {
+            Err(errors) => errors.iter().any(|error| error.location == "end"),
+            Ok(_) => false,
+        },
+    }
 }
 
-impl Display for ScanError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[line {}] Error: {}", self.line, self.message)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn global(interpreter: &Interpreter, name: &str) -> lox::Value {
+        interpreter
+            .globals()
+            .borrow()
+            .get(name, Span::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn repl_keeps_definitions_across_lines() {
+        let mut interpreter = Interpreter::new();
+        let mut input = "var a = 1;\nvar b = a + 1;\n".as_bytes();
+        repl(&mut interpreter, &mut input, Mode::Run, &Options::default()).unwrap();
+        assert_eq!(global(&interpreter, "b"), lox::Value::Number(2.0));
+    }
+
+    #[test]
+    fn repl_reads_until_the_statement_is_complete() {
+        let mut interpreter = Interpreter::new();
+        let mut input = "fun add(a, b) {\n  return a + b;\n}\nvar c = add(1, 2);\n".as_bytes();
+        repl(&mut interpreter, &mut input, Mode::Run, &Options::default()).unwrap();
+        assert!(matches!(
+            global(&interpreter, "add"),
+            lox::Value::Function(_)
+        ));
+        assert_eq!(global(&interpreter, "c"), lox::Value::Number(3.0));
     }
 }
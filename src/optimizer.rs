@@ -0,0 +1,337 @@
+use std::rc::Rc;
+
+use crate::{
+    interpreter::{binary, unary},
+    BinaryOp, Expr, FunctionDecl, Literal, LogicalOp, Stmt, Value,
+};
+
+// folds operators whose operands are all literals, e.g. 2 + 3 into 5. an operation that would be
+// a runtime error, or whose result isn't a literal like 1 / 0, is left for the interpreter to
+// report. calls are never folded, so no side effects are lost
+pub fn optimize(expr: Expr) -> Expr {
+    let fold = |expr: Box<Expr>| Box::new(optimize(*expr));
+    match expr {
+        // a folded expression keeps the span of everything it replaced
+        Expr::Grouping { span, expr } => match optimize(*expr) {
+            Expr::Literal { value, .. } => Expr::Literal { value, span },
+            expr => Expr::Grouping {
+                span,
+                expr: Box::new(expr),
+            },
+        },
+        Expr::Unary { op, span, right } => {
+            let right = optimize(*right);
+            if let Expr::Literal { value, .. } = &right {
+                let span = span.merge(right.span());
+                if let Ok(Some(value)) = unary(op, span, to_value(value)).map(to_literal) {
+                    return Expr::Literal { value, span };
+                }
+            }
+            Expr::Unary {
+                op,
+                span,
+                right: Box::new(right),
+            }
+        }
+        Expr::Binary {
+            left,
+            op,
+            span,
+            right,
+        } => {
+            let (left, right) = (optimize(*left), optimize(*right));
+            if let (Expr::Literal { value: a, .. }, Expr::Literal { value: b, .. }) =
+                (&left, &right)
+            {
+                let by_zero = matches!(op, BinaryOp::Div | BinaryOp::Rem)
+                    && matches!(b, Literal::Number(n) if *n == 0.0) | matches!(b, Literal::Int(0));
+                if !by_zero {
+                    if let Ok(Some(value)) =
+                        binary(to_value(a), op, to_value(b), span).map(to_literal)
+                    {
+                        let span = left.span().merge(right.span());
+                        return Expr::Literal { value, span };
+                    }
+                }
+            }
+            Expr::Binary {
+                left: Box::new(left),
+                op,
+                span,
+                right: Box::new(right),
+            }
+        }
+        Expr::Logical {
+            left,
+            op,
+            span,
+            right,
+        } => match (optimize(*left), optimize(*right)) {
+            (Expr::Literal { value: a, .. }, Expr::Literal { value: b, .. }) => {
+                let truthy = to_value(&a).is_truthy();
+                let value = match (op, truthy) {
+                    (LogicalOp::Or, true) | (LogicalOp::And, false) => a,
+                    _ => b,
+                };
+                Expr::Literal { value, span }
+            }
+            (left, right) => Expr::Logical {
+                left: Box::new(left),
+                op,
+                span,
+                right: Box::new(right),
+            },
+        },
+        Expr::Assign {
+            id,
+            name,
+            span,
+            value,
+        } => Expr::Assign {
+            id,
+            name,
+            span,
+            value: fold(value),
+        },
+        Expr::Comma { left, span, right } => Expr::Comma {
+            left: fold(left),
+            span,
+            right: fold(right),
+        },
+        Expr::Ternary {
+            condition,
+            span,
+            then_branch,
+            else_branch,
+        } => Expr::Ternary {
+            condition: fold(condition),
+            span,
+            then_branch: fold(then_branch),
+            else_branch: fold(else_branch),
+        },
+        Expr::Lambda(decl) => Expr::Lambda(optimize_function(decl)),
+        Expr::Call { callee, span, args } => Expr::Call {
+            callee: fold(callee),
+            span,
+            args: args.into_iter().map(optimize).collect(),
+        },
+        Expr::Get { object, name, span } => Expr::Get {
+            object: fold(object),
+            name,
+            span,
+        },
+        Expr::Set {
+            object,
+            name,
+            span,
+            value,
+        } => Expr::Set {
+            object: fold(object),
+            name,
+            span,
+            value: fold(value),
+        },
+        Expr::List { span, elements } => Expr::List {
+            span,
+            elements: elements.into_iter().map(optimize).collect(),
+        },
+        Expr::Map { span, entries } => Expr::Map {
+            span,
+            entries: entries
+                .into_iter()
+                .map(|(key, value)| (optimize(key), optimize(value)))
+                .collect(),
+        },
+        Expr::Index {
+            object,
+            span,
+            index,
+        } => Expr::Index {
+            object: fold(object),
+            span,
+            index: fold(index),
+        },
+        Expr::SetIndex {
+            object,
+            span,
+            index,
+            value,
+        } => Expr::SetIndex {
+            object: fold(object),
+            span,
+            index: fold(index),
+            value: fold(value),
+        },
+        Expr::Update {
+            op,
+            prefix,
+            span,
+            target,
+        } => Expr::Update {
+            op,
+            prefix,
+            span,
+            target: fold(target),
+        },
+        Expr::CompoundSet {
+            object,
+            name,
+            span,
+            op,
+            value,
+        } => Expr::CompoundSet {
+            object: fold(object),
+            name,
+            span,
+            op,
+            value: fold(value),
+        },
+        expr @ (Expr::Literal { .. }
+        | Expr::Variable { .. }
+        | Expr::This { .. }
+        | Expr::Super { .. }) => expr,
+    }
+}
+
+// optimizes every expression in a program
+pub fn optimize_stmts(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    stmts.into_iter().map(optimize_stmt).collect()
+}
+
+fn optimize_stmt(stmt: Stmt) -> Stmt {
+    let fold = |stmt: Box<Stmt>| Box::new(optimize_stmt(*stmt));
+    match stmt {
+        Stmt::Expression(expr) => Stmt::Expression(optimize(expr)),
+        Stmt::Print { span, expr } => Stmt::Print {
+            span,
+            expr: optimize(expr),
+        },
+        Stmt::Var {
+            name,
+            span,
+            initializer,
+        } => Stmt::Var {
+            name,
+            span,
+            initializer: initializer.map(optimize),
+        },
+        Stmt::Block { span, stmts } => Stmt::Block {
+            span,
+            stmts: optimize_stmts(stmts),
+        },
+        Stmt::If {
+            span,
+            condition,
+            then_branch,
+            else_branch,
+        } => Stmt::If {
+            span,
+            condition: optimize(condition),
+            then_branch: fold(then_branch),
+            else_branch: else_branch.map(fold),
+        },
+        Stmt::While {
+            span,
+            condition,
+            body,
+            increment,
+        } => Stmt::While {
+            span,
+            condition: optimize(condition),
+            body: fold(body),
+            increment: increment.map(optimize),
+        },
+        Stmt::Function(decl) => Stmt::Function(optimize_function(decl)),
+        Stmt::Return { span, value } => Stmt::Return {
+            span,
+            value: value.map(optimize),
+        },
+        Stmt::Class {
+            name,
+            span,
+            superclass,
+            methods,
+        } => Stmt::Class {
+            name,
+            span,
+            superclass,
+            methods: methods.into_iter().map(optimize_function).collect(),
+        },
+        stmt @ (Stmt::Break { .. } | Stmt::Continue { .. }) => stmt,
+    }
+}
+
+// a freshly parsed declaration isn't shared yet; one that is can't be rebuilt, so it's kept
+fn optimize_function(decl: Rc<FunctionDecl>) -> Rc<FunctionDecl> {
+    match Rc::try_unwrap(decl) {
+        Ok(decl) => Rc::new(FunctionDecl {
+            body: optimize_stmts(decl.body),
+            ..decl
+        }),
+        Err(decl) => decl,
+    }
+}
+
+fn to_value(literal: &Literal) -> Value {
+    match literal {
+        Literal::Number(n) => Value::Number(*n),
+        Literal::Int(n) => Value::Int(*n),
+        Literal::Str(s) => Value::Str(s.clone()),
+        Literal::Bool(b) => Value::Bool(*b),
+        Literal::Nil => Value::Nil,
+    }
+}
+
+// non-finite numbers have no literal syntax, so they aren't folded
+fn to_literal(value: Value) -> Option<Literal> {
+    let literal = match value {
+        Value::Number(n) if n.is_finite() => Literal::Number(n),
+        Value::Int(n) => Literal::Int(n),
+        Value::Str(s) => Literal::Str(s),
+        Value::Bool(b) => Literal::Bool(b),
+        Value::Nil => Literal::Nil,
+        _ => return None,
+    };
+    Some(literal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, Scanner};
+
+    fn folded(src: &str) -> String {
+        let expr = Parser::new(Scanner::new(src).scan().unwrap())
+            .parse()
+            .unwrap();
+        optimize(expr).to_string()
+    }
+
+    #[test]
+    fn constant_subexpressions_are_folded() {
+        assert_eq!(folded("2 + 3"), "5");
+        assert_eq!(folded("!true"), "false");
+        assert_eq!(folded("\"a\" + \"b\""), "\"ab\"");
+        assert_eq!(folded("(1 + 2) * x - -4"), "(- (* 3 x) -4)");
+        assert_eq!(folded("x ? 2 * 3 : nil == nil"), "(?: x 6 true)");
+    }
+
+    #[test]
+    fn runtime_errors_and_calls_are_left_alone() {
+        assert_eq!(folded("1 / 0"), "(/ 1 0)");
+        assert_eq!(folded("-\"a\""), "(- \"a\")");
+        assert_eq!(folded("f(1 + 1) + 2 * 2"), "(+ (call f 2) 4)");
+        assert_eq!(folded("clock() * 0"), "(* (call clock) 0)");
+    }
+
+    #[test]
+    fn folded_literal_keeps_the_span_it_replaces() {
+        let expr = Parser::new(Scanner::new("x + (1 + 2)").scan().unwrap())
+            .parse()
+            .unwrap();
+        let Expr::Binary { right, .. } = optimize(expr) else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!((right.span().start, right.span().end), (4, 11));
+    }
+}
@@ -0,0 +1,1795 @@
+use std::{
+    fmt::Display,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{ExprVisitor, Span, Token, TokenType};
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type"))]
+pub enum Stmt {
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::json::expression_stmt")
+    )]
+    Expression(Expr),
+    // with the span of the keyword
+    Print {
+        span: Span,
+        expr: Expr,
+    },
+    Var {
+        name: String,
+        span: Span,
+        initializer: Option<Expr>,
+    },
+    // with the span from the opening brace to the closing one
+    Block {
+        span: Span,
+        stmts: Vec<Stmt>,
+    },
+    // with the span of the keyword, as for while
+    If {
+        span: Span,
+        condition: Expr,
+        #[cfg_attr(feature = "serde", serde(rename = "then"))]
+        then_branch: Box<Stmt>,
+        #[cfg_attr(feature = "serde", serde(rename = "else"))]
+        else_branch: Option<Box<Stmt>>,
+    },
+    // for loops desugar to one, with the span of the for keyword
+    While {
+        span: Span,
+        condition: Expr,
+        body: Box<Stmt>,
+        // run after the body on every iteration, including ones cut short by continue
+        increment: Option<Expr>,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
+    },
+    Function(Rc<FunctionDecl>),
+    Return {
+        span: Span,
+        value: Option<Expr>,
+    },
+    Class {
+        name: String,
+        span: Span,
+        superclass: Option<Expr>,
+        methods: Vec<Rc<FunctionDecl>>,
+    },
+}
+
+// shared with every function value created from the declaration
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FunctionDecl {
+    pub name: String,
+    pub span: Span,
+    // each parameter's name and where it's declared
+    pub params: Vec<(String, Span)>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type"))]
+pub enum Expr {
+    Literal {
+        value: Literal,
+        span: Span,
+    },
+    Variable {
+        #[cfg_attr(feature = "serde", serde(skip))]
+        id: ExprId,
+        name: String,
+        span: Span,
+    },
+    Assign {
+        #[cfg_attr(feature = "serde", serde(skip))]
+        id: ExprId,
+        name: String,
+        span: Span,
+        value: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        span: Span,
+        right: Box<Expr>,
+    },
+    // a binary expression's span is its operator's, so errors in it point there
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        span: Span,
+        right: Box<Expr>,
+    },
+    Logical {
+        left: Box<Expr>,
+        op: LogicalOp,
+        span: Span,
+        right: Box<Expr>,
+    },
+    Comma {
+        left: Box<Expr>,
+        span: Span,
+        right: Box<Expr>,
+    },
+    Ternary {
+        condition: Box<Expr>,
+        span: Span,
+        #[cfg_attr(feature = "serde", serde(rename = "then"))]
+        then_branch: Box<Expr>,
+        #[cfg_attr(feature = "serde", serde(rename = "else"))]
+        else_branch: Box<Expr>,
+    },
+    // an anonymous function; its decl has an empty name
+    Lambda(Rc<FunctionDecl>),
+    // with the span of the parenthesized arguments
+    Call {
+        callee: Box<Expr>,
+        span: Span,
+        args: Vec<Expr>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: String,
+        span: Span,
+    },
+    Set {
+        object: Box<Expr>,
+        name: String,
+        span: Span,
+        value: Box<Expr>,
+    },
+    // lists, maps and groupings span their brackets, braces or parens
+    List {
+        span: Span,
+        elements: Vec<Expr>,
+    },
+    Map {
+        span: Span,
+        entries: Vec<(Expr, Expr)>,
+    },
+    // object[index], with the span of the bracketed index
+    Index {
+        object: Box<Expr>,
+        span: Span,
+        index: Box<Expr>,
+    },
+    SetIndex {
+        object: Box<Expr>,
+        span: Span,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    // ++x, x++, --x or x--, where x is a Variable or Get
+    Update {
+        op: UpdateOp,
+        prefix: bool,
+        span: Span,
+        target: Box<Expr>,
+    },
+    // object.name op= value, which evaluates object only once
+    CompoundSet {
+        object: Box<Expr>,
+        name: String,
+        span: Span,
+        op: BinaryOp,
+        value: Box<Expr>,
+    },
+    This {
+        #[cfg_attr(feature = "serde", serde(skip))]
+        id: ExprId,
+        span: Span,
+    },
+    // with the span of super.method
+    Super {
+        #[cfg_attr(feature = "serde", serde(skip))]
+        id: ExprId,
+        span: Span,
+        method: String,
+    },
+    Grouping {
+        span: Span,
+        expr: Box<Expr>,
+    },
+}
+
+// identifies a variable reference so the resolver can record its scope distance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    // unique across parsers, so ids from separate REPL lines never collide
+    fn fresh() -> ExprId {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        ExprId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
+pub enum Literal {
+    Number(f64),
+    Int(i64),
+    Str(String),
+    Bool(bool),
+    Nil,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+    // converts to a string, only produced by interpolation
+    ToStr,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UpdateOp {
+    Inc,
+    Dec,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BinaryOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl Stmt {
+    // the source the statement covers, less any trailing semicolon; a var, function or class
+    // declaration starts at its name
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Expression(expr) => expr.span(),
+            Stmt::Print { span, expr } => span.merge(expr.span()),
+            Stmt::Var {
+                span, initializer, ..
+            } => initializer
+                .as_ref()
+                .map_or(*span, |expr| span.merge(expr.span())),
+            Stmt::Block { span, .. }
+            | Stmt::Break { span }
+            | Stmt::Continue { span }
+            | Stmt::Class { span, .. } => *span,
+            Stmt::If {
+                span,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let span = span.merge(then_branch.span());
+                else_branch
+                    .as_ref()
+                    .map_or(span, |stmt| span.merge(stmt.span()))
+            }
+            Stmt::While { span, body, .. } => span.merge(body.span()),
+            Stmt::Function(decl) => decl.span,
+            Stmt::Return { span, value } => {
+                value.as_ref().map_or(*span, |expr| span.merge(expr.span()))
+            }
+        }
+    }
+}
+
+impl Expr {
+    // the source the expression covers, e.g. all of a.b(c) rather than just its arguments
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Literal { span, .. }
+            | Expr::Variable { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Comma { span, .. }
+            | Expr::Ternary { span, .. }
+            | Expr::List { span, .. }
+            | Expr::Map { span, .. }
+            | Expr::This { span, .. }
+            | Expr::Super { span, .. }
+            | Expr::Grouping { span, .. } => *span,
+            Expr::Lambda(decl) => decl.span,
+            Expr::Binary { left, right, .. } => left.span().merge(right.span()),
+            Expr::Assign { span, value, .. } => span.merge(value.span()),
+            Expr::Unary { span, right, .. } => span.merge(right.span()),
+            Expr::Update { span, target, .. } => span.merge(target.span()),
+            Expr::Call {
+                callee: object,
+                span,
+                ..
+            }
+            | Expr::Get { object, span, .. }
+            | Expr::Index { object, span, .. } => object.span().merge(*span),
+            Expr::Set { object, value, .. }
+            | Expr::SetIndex { object, value, .. }
+            | Expr::CompoundSet { object, value, .. } => object.span().merge(value.span()),
+        }
+    }
+}
+
+// prints the tree in fully parenthesized prefix form, e.g. (* (- 1) (group (+ 2 3)))
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.accept(&mut AstPrinter))
+    }
+}
+
+struct AstPrinter;
+
+impl AstPrinter {
+    // (head a b ...), printing each expression in parens
+    fn parenthesize<'a>(
+        &mut self,
+        head: &str,
+        exprs: impl IntoIterator<Item = &'a Expr>,
+    ) -> String {
+        let mut out = format!("({}", head);
+        for expr in exprs {
+            out.push(' ');
+            out.push_str(&expr.accept(self));
+        }
+        out.push(')');
+        out
+    }
+}
+
+impl ExprVisitor<String> for AstPrinter {
+    fn visit_literal_expr(&mut self, value: &Literal, _: Span) -> String {
+        value.to_string()
+    }
+
+    fn visit_variable_expr(&mut self, _: ExprId, name: &str, _: Span) -> String {
+        name.to_string()
+    }
+
+    fn visit_assign_expr(&mut self, _: ExprId, name: &str, _: Span, value: &Expr) -> String {
+        format!("(= {} {})", name, value.accept(self))
+    }
+
+    fn visit_unary_expr(&mut self, op: UnaryOp, _: Span, right: &Expr) -> String {
+        self.parenthesize(&op.to_string(), [right])
+    }
+
+    fn visit_binary_expr(&mut self, left: &Expr, op: BinaryOp, _: Span, right: &Expr) -> String {
+        self.parenthesize(&op.to_string(), [left, right])
+    }
+
+    fn visit_logical_expr(&mut self, left: &Expr, op: LogicalOp, _: Span, right: &Expr) -> String {
+        self.parenthesize(&op.to_string(), [left, right])
+    }
+
+    fn visit_comma_expr(&mut self, left: &Expr, _: Span, right: &Expr) -> String {
+        self.parenthesize(",", [left, right])
+    }
+
+    fn visit_ternary_expr(
+        &mut self,
+        condition: &Expr,
+        _: Span,
+        then_branch: &Expr,
+        else_branch: &Expr,
+    ) -> String {
+        self.parenthesize("?:", [condition, then_branch, else_branch])
+    }
+
+    fn visit_lambda_expr(&mut self, decl: &Rc<FunctionDecl>) -> String {
+        let params: Vec<_> = decl.params.iter().map(|(name, _)| name.as_str()).collect();
+        format!("(fun ({}))", params.join(" "))
+    }
+
+    fn visit_call_expr(&mut self, callee: &Expr, _: Span, args: &[Expr]) -> String {
+        self.parenthesize("call", std::iter::once(callee).chain(args))
+    }
+
+    fn visit_get_expr(&mut self, object: &Expr, name: &str, _: Span) -> String {
+        format!("(. {} {})", object.accept(self), name)
+    }
+
+    fn visit_set_expr(&mut self, object: &Expr, name: &str, _: Span, value: &Expr) -> String {
+        format!(
+            "(= (. {} {}) {})",
+            object.accept(self),
+            name,
+            value.accept(self)
+        )
+    }
+
+    fn visit_list_expr(&mut self, _: Span, elements: &[Expr]) -> String {
+        self.parenthesize("list", elements)
+    }
+
+    fn visit_map_expr(&mut self, _: Span, entries: &[(Expr, Expr)]) -> String {
+        self.parenthesize("map", entries.iter().flat_map(|(key, value)| [key, value]))
+    }
+
+    fn visit_index_expr(&mut self, object: &Expr, _: Span, index: &Expr) -> String {
+        self.parenthesize("[]", [object, index])
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        _: Span,
+        index: &Expr,
+        value: &Expr,
+    ) -> String {
+        let target = self.parenthesize("[]", [object, index]);
+        format!("(= {} {})", target, value.accept(self))
+    }
+
+    fn visit_update_expr(&mut self, op: UpdateOp, prefix: bool, _: Span, target: &Expr) -> String {
+        if prefix {
+            format!("({} {})", op, target.accept(self))
+        } else {
+            format!("({} {})", target.accept(self), op)
+        }
+    }
+
+    fn visit_compound_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &str,
+        _: Span,
+        op: BinaryOp,
+        value: &Expr,
+    ) -> String {
+        format!(
+            "({}= (. {} {}) {})",
+            op,
+            object.accept(self),
+            name,
+            value.accept(self)
+        )
+    }
+
+    fn visit_this_expr(&mut self, _: ExprId, _: Span) -> String {
+        "this".to_string()
+    }
+
+    fn visit_super_expr(&mut self, _: ExprId, _: Span, method: &str) -> String {
+        format!("(super {})", method)
+    }
+
+    fn visit_grouping_expr(&mut self, _: Span, expr: &Expr) -> String {
+        self.parenthesize("group", [expr])
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Number(n) => write!(f, "{}", n),
+            Literal::Int(n) => write!(f, "{}", n),
+            Literal::Str(s) => write!(f, "\"{}\"", s),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "!",
+            UnaryOp::BitNot => "~",
+            UnaryOp::ToStr => "str",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl Display for UpdateOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            UpdateOp::Inc => "++",
+            UpdateOp::Dec => "--",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl Display for LogicalOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            LogicalOp::And => "and",
+            LogicalOp::Or => "or",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Pow => "**",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+// binding power of binary operators, loosest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Equality,
+    // tighter than equality, unlike C, so 6 & 3 == 2 compares the result
+    BitOr,
+    BitXor,
+    BitAnd,
+    Comparison,
+    Shift,
+    Term,
+    Factor,
+    // binds tighter than any binary operator but **, so operands of a factor stop at the next one
+    Unary,
+}
+
+impl Precedence {
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Equality => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Shift,
+            Precedence::Shift => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor | Precedence::Unary => Precedence::Unary,
+        }
+    }
+}
+
+fn binary_op(typ: &TokenType) -> Option<(BinaryOp, Precedence)> {
+    let op = match typ {
+        TokenType::EqualEqual => (BinaryOp::Equal, Precedence::Equality),
+        TokenType::BangEqual => (BinaryOp::NotEqual, Precedence::Equality),
+        TokenType::Less => (BinaryOp::Less, Precedence::Comparison),
+        TokenType::LessEqual => (BinaryOp::LessEqual, Precedence::Comparison),
+        TokenType::Greater => (BinaryOp::Greater, Precedence::Comparison),
+        TokenType::GreaterEqual => (BinaryOp::GreaterEqual, Precedence::Comparison),
+        TokenType::Pipe => (BinaryOp::BitOr, Precedence::BitOr),
+        TokenType::Caret => (BinaryOp::BitXor, Precedence::BitXor),
+        TokenType::Amp => (BinaryOp::BitAnd, Precedence::BitAnd),
+        TokenType::LessLess => (BinaryOp::Shl, Precedence::Shift),
+        TokenType::GreaterGreater => (BinaryOp::Shr, Precedence::Shift),
+        TokenType::Plus => (BinaryOp::Add, Precedence::Term),
+        TokenType::Minus => (BinaryOp::Sub, Precedence::Term),
+        TokenType::Star => (BinaryOp::Mul, Precedence::Factor),
+        TokenType::Slash => (BinaryOp::Div, Precedence::Factor),
+        TokenType::Percent => (BinaryOp::Rem, Precedence::Factor),
+        _ => return None,
+    };
+    Some(op)
+}
+
+pub struct Parser<'src> {
+    tokens: Vec<Token<'src>>,
+    curr: usize,
+    errors: Vec<ParseError>,
+    // how deep the current recursive descent is, bounded so hostile input can't overflow the stack
+    depth: usize,
+    max_depth: usize,
+    // whether var x; is an error rather than declaring x as nil
+    strict_declarations: bool,
+}
+
+impl<'src> Parser<'src> {
+    // tokens must end with an Eof token, as produced by Scanner
+    pub fn new(tokens: Vec<Token<'src>>) -> Parser<'src> {
+        Parser {
+            tokens,
+            curr: 0,
+            errors: vec![],
+            depth: 0,
+            max_depth: 256,
+            strict_declarations: false,
+        }
+    }
+
+    // bounds how deeply the tree may nest, so neither parsing nor walking it later can overflow
+    // the stack. every pass of a left-associative chain like 1 + 2 + 3 or a.b.c counts as a
+    // level, and a call counts as two, one for the call and one for its arguments
+    pub fn with_max_depth(mut self, max_depth: usize) -> Parser<'src> {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_strict_declarations(mut self, strict_declarations: bool) -> Parser<'src> {
+        self.strict_declarations = strict_declarations;
+        self
+    }
+
+    // synchronizes after each malformed declaration, so every error in the program is returned
+    pub fn parse_program(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let mut stmts = vec![];
+        while !self.check(TokenType::Eof) {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+        if self.errors.is_empty() {
+            Ok(stmts)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.expression()?;
+        if !self.check(TokenType::Eof) {
+            return Err(self.error("Expect end of expression."));
+        }
+        Ok(expr)
+    }
+
+    // parses one declaration, recording the error and skipping ahead if it is malformed
+    fn declaration(&mut self) -> Option<Stmt> {
+        let stmt = match self.peek().typ {
+            TokenType::Var => {
+                self.advance();
+                self.var_declaration()
+            }
+            // without a name, it's an anonymous function starting an expression statement
+            TokenType::Fun if matches!(self.peek_next().typ, TokenType::Ident) => {
+                self.advance();
+                self.function("function").map(Stmt::Function)
+            }
+            TokenType::Class => {
+                self.advance();
+                self.class_declaration()
+            }
+            _ => self.statement(),
+        };
+        match stmt {
+            Ok(stmt) => Some(stmt),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let token = self.consume(TokenType::Ident, "Expect class name.")?;
+        let (name, span) = (token.lexeme.to_string(), token.span);
+        let superclass = if self.match_token(&[TokenType::Less]) {
+            let token = self.consume(TokenType::Ident, "Expect superclass name.")?;
+            if token.lexeme == name {
+                return Err(self.error_previous("A class can't inherit from itself."));
+            }
+            Some(Expr::Variable {
+                id: ExprId::fresh(),
+                name: token.lexeme.to_string(),
+                span: token.span,
+            })
+        } else {
+            None
+        };
+        self.consume(TokenType::LBrace, "Expect '{' before class body.")?;
+        let mut methods = vec![];
+        while !matches!(self.peek().typ, TokenType::RBrace | TokenType::Eof) {
+            methods.push(self.function("method")?);
+        }
+        self.consume(TokenType::RBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class {
+            name,
+            span,
+            superclass,
+            methods,
+        })
+    }
+
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionDecl>, ParseError> {
+        let token = self.consume(TokenType::Ident, &format!("Expect {} name.", kind))?;
+        let (name, span) = (token.lexeme.to_string(), token.span);
+        self.consume(
+            TokenType::LParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+        self.function_rest(name, span, kind)
+    }
+
+    // parses the parameters and body, after the opening parenthesis
+    fn function_rest(
+        &mut self,
+        name: String,
+        span: Span,
+        kind: &str,
+    ) -> Result<Rc<FunctionDecl>, ParseError> {
+        let mut params = vec![];
+        if !self.check(TokenType::RParen) {
+            loop {
+                if params.len() >= 255 {
+                    self.errors
+                        .push(self.error("Can't have more than 255 parameters."));
+                }
+                let param = self.consume(TokenType::Ident, "Expect parameter name.")?;
+                params.push((param.lexeme.to_string(), param.span));
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RParen, "Expect ')' after parameters.")?;
+        self.consume(
+            TokenType::LBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.nested(Self::block)?;
+        Ok(Rc::new(FunctionDecl {
+            name,
+            span,
+            params,
+            body,
+        }))
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let token = self.consume(TokenType::Ident, "Expect variable name.")?;
+        let (name, span) = (token.lexeme.to_string(), token.span);
+        let initializer = if self.match_token(&[TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            if self.strict_declarations {
+                // the declaration still parses fine, so there's nothing to synchronize past
+                self.errors
+                    .push(self.error_previous("Variable must be initialized."));
+            }
+            None
+        };
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Var {
+            name,
+            span,
+            initializer,
+        })
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        match self.peek().typ {
+            TokenType::Print => {
+                let span = self.advance().span;
+                let expr = self.expression()?;
+                self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+                Ok(Stmt::Print { span, expr })
+            }
+            TokenType::LBrace => {
+                let start = self.advance().span;
+                let stmts = self.nested(Self::block)?;
+                Ok(Stmt::Block {
+                    span: start.merge(self.previous().span),
+                    stmts,
+                })
+            }
+            TokenType::If => {
+                let span = self.advance().span;
+                self.if_statement(span)
+            }
+            TokenType::While => {
+                let span = self.advance().span;
+                self.while_statement(span)
+            }
+            TokenType::For => {
+                let span = self.advance().span;
+                self.for_statement(span)
+            }
+            TokenType::Break => {
+                let span = self.advance().span;
+                self.consume(TokenType::Semicolon, "Expect ';' after 'break'.")?;
+                Ok(Stmt::Break { span })
+            }
+            TokenType::Continue => {
+                let span = self.advance().span;
+                self.consume(TokenType::Semicolon, "Expect ';' after 'continue'.")?;
+                Ok(Stmt::Continue { span })
+            }
+            TokenType::Return => {
+                let span = self.advance().span;
+                let value = match self.peek().typ {
+                    TokenType::Semicolon => None,
+                    _ => Some(self.expression()?),
+                };
+                self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+                Ok(Stmt::Return { span, value })
+            }
+            _ => {
+                let expr = self.expression()?;
+                self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+                Ok(Stmt::Expression(expr))
+            }
+        }
+    }
+
+    fn if_statement(&mut self, span: Span) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RParen, "Expect ')' after if condition.")?;
+        let then_branch = Box::new(self.nested(Self::statement)?);
+        let else_branch = if self.match_token(&[TokenType::Else]) {
+            Some(Box::new(self.nested(Self::statement)?))
+        } else {
+            None
+        };
+        Ok(Stmt::If {
+            span,
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn while_statement(&mut self, span: Span) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.nested(Self::statement)?);
+        Ok(Stmt::While {
+            span,
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    // desugars to { initializer; while (condition) body } with the increment kept on the loop
+    fn for_statement(&mut self, span: Span) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LParen, "Expect '(' after 'for'.")?;
+        let initializer = match self.peek().typ {
+            TokenType::Semicolon => {
+                self.advance();
+                None
+            }
+            TokenType::Var => {
+                self.advance();
+                Some(self.var_declaration()?)
+            }
+            _ => {
+                let expr = self.expression()?;
+                self.consume(TokenType::Semicolon, "Expect ';' after loop initializer.")?;
+                Some(Stmt::Expression(expr))
+            }
+        };
+        // an empty condition loops forever
+        let condition = match self.peek().typ {
+            TokenType::Semicolon => Expr::Literal {
+                value: Literal::Bool(true),
+                span: self.peek().span,
+            },
+            _ => self.expression()?,
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+        let increment = match self.peek().typ {
+            TokenType::RParen => None,
+            _ => Some(self.expression()?),
+        };
+        self.consume(TokenType::RParen, "Expect ')' after for clauses.")?;
+
+        let mut body = Stmt::While {
+            span,
+            condition,
+            body: Box::new(self.nested(Self::statement)?),
+            increment,
+        };
+        if let Some(initializer) = initializer {
+            body = Stmt::Block {
+                span: body.span(),
+                stmts: vec![initializer, body],
+            };
+        }
+        Ok(body)
+    }
+
+    // the opening brace has already been consumed
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut stmts = vec![];
+        while !matches!(self.peek().typ, TokenType::RBrace | TokenType::Eof) {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+        self.consume(TokenType::RBrace, "Expect '}' after block.")?;
+        Ok(stmts)
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.nested(Self::comma)
+    }
+
+    fn comma(&mut self) -> Result<Expr, ParseError> {
+        self.chain(|this| {
+            let mut left = this.assignment()?;
+            while this.check(TokenType::Comma) {
+                this.deepen()?;
+                this.advance();
+                let right = this.assignment()?;
+                left = Expr::Comma {
+                    span: left.span().merge(right.span()),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                };
+            }
+            Ok(left)
+        })
+    }
+
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.ternary()?;
+        // None for plain assignment, or the operator of a compound one
+        let op = match self.peek().typ {
+            TokenType::Equal => None,
+            TokenType::PlusEqual => Some(BinaryOp::Add),
+            TokenType::MinusEqual => Some(BinaryOp::Sub),
+            TokenType::StarEqual => Some(BinaryOp::Mul),
+            TokenType::SlashEqual => Some(BinaryOp::Div),
+            TokenType::PercentEqual => Some(BinaryOp::Rem),
+            _ => return Ok(expr),
+        };
+        let equals = self.curr;
+        self.advance();
+        let value = self.nested(Self::assignment)?;
+        match (expr, op) {
+            (Expr::Variable { name, span, .. }, op) => {
+                // x op= value is just x = x op value, since reading x has no side effects
+                let value = match op {
+                    None => value,
+                    Some(op) => Expr::Binary {
+                        left: Box::new(Expr::Variable {
+                            id: ExprId::fresh(),
+                            name: name.clone(),
+                            span,
+                        }),
+                        op,
+                        span: self.tokens[equals].span,
+                        right: Box::new(value),
+                    },
+                };
+                Ok(Expr::Assign {
+                    id: ExprId::fresh(),
+                    name,
+                    span,
+                    value: Box::new(value),
+                })
+            }
+            (
+                Expr::Index {
+                    object,
+                    span,
+                    index,
+                },
+                None,
+            ) => Ok(Expr::SetIndex {
+                object,
+                span,
+                index,
+                value: Box::new(value),
+            }),
+            (Expr::Get { object, name, span }, None) => Ok(Expr::Set {
+                object,
+                name,
+                span,
+                value: Box::new(value),
+            }),
+            (Expr::Get { object, name, span }, Some(op)) => Ok(Expr::CompoundSet {
+                object,
+                name,
+                span,
+                op,
+                value: Box::new(value),
+            }),
+            _ => Err(error_at(&self.tokens[equals], "Invalid assignment target.")),
+        }
+    }
+
+    fn ternary(&mut self) -> Result<Expr, ParseError> {
+        let condition = self.or()?;
+        if !self.check(TokenType::Question) {
+            return Ok(condition);
+        }
+        self.advance();
+        let then_branch = self.expression()?;
+        self.consume(TokenType::Colon, "Expect ':' in ternary.")?;
+        // right-associative, so a ? b : c ? d : e nests in the else branch
+        let else_branch = self.nested(Self::ternary)?;
+        Ok(Expr::Ternary {
+            span: condition.span().merge(else_branch.span()),
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        })
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        self.chain(|this| {
+            let mut left = this.and()?;
+            while this.check(TokenType::Or) {
+                this.deepen()?;
+                this.advance();
+                let right = this.and()?;
+                left = Expr::Logical {
+                    span: left.span().merge(right.span()),
+                    left: Box::new(left),
+                    op: LogicalOp::Or,
+                    right: Box::new(right),
+                };
+            }
+            Ok(left)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        self.chain(|this| {
+            let mut left = this.binary(Precedence::Equality)?;
+            while this.check(TokenType::And) {
+                this.deepen()?;
+                this.advance();
+                let right = this.binary(Precedence::Equality)?;
+                left = Expr::Logical {
+                    span: left.span().merge(right.span()),
+                    left: Box::new(left),
+                    op: LogicalOp::And,
+                    right: Box::new(right),
+                };
+            }
+            Ok(left)
+        })
+    }
+
+    fn binary(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        self.chain(|this| {
+            let mut left = this.unary()?;
+            while let Some((op, prec)) = binary_op(&this.peek().typ) {
+                if prec < min {
+                    break;
+                }
+                this.deepen()?;
+                let span = this.advance().span;
+                let right = this.binary(prec.next())?;
+                left = Expr::Binary {
+                    span,
+                    left: Box::new(left),
+                    op,
+                    right: Box::new(right),
+                };
+            }
+            Ok(left)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let op = match self.peek().typ {
+            TokenType::Bang => UnaryOp::Not,
+            TokenType::Minus => UnaryOp::Neg,
+            TokenType::Tilde => UnaryOp::BitNot,
+            TokenType::PlusPlus | TokenType::MinusMinus => {
+                let operator = self.curr;
+                self.advance();
+                let target = self.nested(Self::unary)?;
+                return update(&self.tokens[operator], true, target);
+            }
+            _ => return self.power(),
+        };
+        let span = self.advance().span;
+        let right = self.nested(Self::unary)?;
+        Ok(Expr::Unary {
+            op,
+            span,
+            right: Box::new(right),
+        })
+    }
+
+    // ** is right-associative and tighter than a unary operator on its left, so -2 ** 2 is -4,
+    // but its exponent may be negated, as in 2 ** -1
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.postfix()?;
+        if !self.check(TokenType::StarStar) {
+            return Ok(base);
+        }
+        let span = self.advance().span;
+        let exponent = self.nested(Self::unary)?;
+        Ok(Expr::Binary {
+            span,
+            left: Box::new(base),
+            op: BinaryOp::Pow,
+            right: Box::new(exponent),
+        })
+    }
+
+    fn postfix(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.call()?;
+        if !matches!(self.peek().typ, TokenType::PlusPlus | TokenType::MinusMinus) {
+            return Ok(expr);
+        }
+        let operator = self.curr;
+        self.advance();
+        update(&self.tokens[operator], false, expr)
+    }
+
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        self.chain(|this| {
+            let mut expr = this.primary()?;
+            loop {
+                if !matches!(
+                    this.peek().typ,
+                    TokenType::LParen | TokenType::LBracket | TokenType::Dot
+                ) {
+                    return Ok(expr);
+                }
+                this.deepen()?;
+                let token = this.advance();
+                let span = token.span;
+                match token.typ {
+                    TokenType::LParen => expr = this.finish_call(expr, span)?,
+                    TokenType::LBracket => {
+                        let index = this.expression()?;
+                        let end = this.consume(TokenType::RBracket, "Expect ']' after index.")?;
+                        expr = Expr::Index {
+                            span: span.merge(end.span),
+                            object: Box::new(expr),
+                            index: Box::new(index),
+                        };
+                    }
+                    _ => {
+                        let token =
+                            this.consume(TokenType::Ident, "Expect property name after '.'.")?;
+                        expr = Expr::Get {
+                            object: Box::new(expr),
+                            name: token.lexeme.to_string(),
+                            span: token.span,
+                        };
+                    }
+                }
+            }
+        })
+    }
+
+    // the opening paren, at start, has already been consumed
+    fn finish_call(&mut self, callee: Expr, start: Span) -> Result<Expr, ParseError> {
+        let mut args = vec![];
+        if !self.check(TokenType::RParen) {
+            loop {
+                if args.len() >= 255 {
+                    self.errors
+                        .push(self.error("Can't have more than 255 arguments."));
+                }
+                // commas here separate arguments rather than sequencing them
+                args.push(self.nested(Self::assignment)?);
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let end = self.consume(TokenType::RParen, "Expect ')' after arguments.")?;
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            span: start.merge(end.span),
+            args,
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let literal = match &self.peek().typ {
+            TokenType::Number(n) => Literal::Number(*n),
+            TokenType::Int(n) => Literal::Int(*n),
+            TokenType::Str(s) => Literal::Str(s.clone()),
+            TokenType::Interp(_) => return self.interpolation(),
+            TokenType::True => Literal::Bool(true),
+            TokenType::False => Literal::Bool(false),
+            TokenType::Nil => Literal::Nil,
+            TokenType::Fun => {
+                let span = self.advance().span;
+                self.consume(TokenType::LParen, "Expect '(' after 'fun'.")?;
+                return Ok(Expr::Lambda(self.function_rest(
+                    String::new(),
+                    span,
+                    "function",
+                )?));
+            }
+            TokenType::Ident => {
+                let token = self.advance();
+                return Ok(Expr::Variable {
+                    id: ExprId::fresh(),
+                    name: token.lexeme.to_string(),
+                    span: token.span,
+                });
+            }
+            TokenType::This => {
+                let span = self.advance().span;
+                return Ok(Expr::This {
+                    id: ExprId::fresh(),
+                    span,
+                });
+            }
+            TokenType::Super => {
+                let span = self.advance().span;
+                self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+                let method = self.consume(TokenType::Ident, "Expect superclass method name.")?;
+                return Ok(Expr::Super {
+                    id: ExprId::fresh(),
+                    span: span.merge(method.span),
+                    method: method.lexeme.to_string(),
+                });
+            }
+            TokenType::LParen => {
+                let start = self.advance().span;
+                let expr = self.expression()?;
+                let end = self.consume(TokenType::RParen, "Expect ')' after expression.")?;
+                return Ok(Expr::Grouping {
+                    span: start.merge(end.span),
+                    expr: Box::new(expr),
+                });
+            }
+            TokenType::LBracket => {
+                let start = self.advance().span;
+                let mut elements = vec![];
+                if !self.check(TokenType::RBracket) {
+                    loop {
+                        // commas here separate elements rather than sequencing them
+                        elements.push(self.nested(Self::assignment)?);
+                        if !self.match_token(&[TokenType::Comma]) {
+                            break;
+                        }
+                    }
+                }
+                let end = self.consume(TokenType::RBracket, "Expect ']' after list elements.")?;
+                return Ok(Expr::List {
+                    span: start.merge(end.span),
+                    elements,
+                });
+            }
+            // only in expression position, since a statement starting with '{' is a block
+            TokenType::LBrace => {
+                let start = self.advance().span;
+                let mut entries = vec![];
+                if !self.check(TokenType::RBrace) {
+                    loop {
+                        let key = self.nested(Self::assignment)?;
+                        self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                        entries.push((key, self.nested(Self::assignment)?));
+                        if !self.match_token(&[TokenType::Comma]) {
+                            break;
+                        }
+                    }
+                }
+                let end = self.consume(TokenType::RBrace, "Expect '}' after map entries.")?;
+                return Ok(Expr::Map {
+                    span: start.merge(end.span),
+                    entries,
+                });
+            }
+            _ => return Err(self.error("Expect expression.")),
+        };
+        let span = self.advance().span;
+        Ok(Expr::Literal {
+            value: literal,
+            span,
+        })
+    }
+
+    // "a${b}c" scans as Interp("a") b Str("c") and becomes "a" + str(b) + "c", where each + has
+    // the span of the fragment that adds it
+    fn interpolation(&mut self) -> Result<Expr, ParseError> {
+        self.chain(|this| {
+            let mut expr: Option<Expr> = None;
+            let concat = |left: Option<Expr>, right: Expr, span: Span| match left {
+                Some(left) => Expr::Binary {
+                    span,
+                    left: Box::new(left),
+                    op: BinaryOp::Add,
+                    right: Box::new(right),
+                },
+                None => right,
+            };
+            loop {
+                // each fragment adds a fragment and a value to the chain of concatenations
+                this.deepen()?;
+                this.deepen()?;
+                let token = this.advance();
+                let span = token.span;
+                let (fragment, done) = match &token.typ {
+                    TokenType::Interp(s) => (s.clone(), false),
+                    TokenType::Str(s) => (s.clone(), true),
+                    _ => unreachable!("only called at a fragment"),
+                };
+                let fragment = Expr::Literal {
+                    value: Literal::Str(fragment),
+                    span,
+                };
+                expr = Some(concat(expr, fragment, span));
+                if done {
+                    return Ok(expr.unwrap());
+                }
+                let value = this.expression()?;
+                let value = Expr::Unary {
+                    op: UnaryOp::ToStr,
+                    span,
+                    right: Box::new(value),
+                };
+                expr = Some(concat(expr, value, span));
+                // the scanner turns the closing brace into the start of the next fragment
+                if !(matches!(this.peek().typ, TokenType::Interp(_) | TokenType::Str(_))
+                    && this.peek().lexeme.starts_with('}'))
+                {
+                    return Err(this.error("Expect '}' after interpolated expression."));
+                }
+            }
+        })
+    }
+
+    fn consume(&mut self, expected: TokenType, message: &str) -> Result<&Token<'src>, ParseError> {
+        if self.check(expected) {
+            Ok(self.advance())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    // the lookahead helpers are public so code driving a parser can see what comes next
+    pub fn peek(&self) -> &Token<'src> {
+        &self.tokens[self.curr]
+    }
+
+    // the token after the current one, or Eof at the end
+    pub fn peek_next(&self) -> &Token<'src> {
+        self.tokens
+            .get(self.curr + 1)
+            .unwrap_or(&self.tokens[self.tokens.len() - 1])
+    }
+
+    pub fn check(&self, expected: TokenType) -> bool {
+        self.peek().typ == expected
+    }
+
+    // advances past the current token if it is any of expected
+    pub fn match_token(&mut self, expected: &[TokenType]) -> bool {
+        if expected.contains(&self.peek().typ) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    // the token just advanced past
+    fn previous(&self) -> &Token<'src> {
+        &self.tokens[self.curr - 1]
+    }
+
+    fn advance(&mut self) -> &Token<'src> {
+        let token = &self.tokens[self.curr];
+        if !matches!(token.typ, TokenType::Eof) {
+            self.curr += 1;
+        }
+        token
+    }
+
+    // runs one level of recursive descent, failing once nesting is too deep
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.deepen()?;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // runs a left-associative loop, each pass of which must call deepen, since it nests what's
+    // been parsed so far one level deeper in the tree without recursing
+    fn chain(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let result = parse(self);
+        self.depth = depth;
+        result
+    }
+
+    fn deepen(&mut self) -> Result<(), ParseError> {
+        if self.depth >= self.max_depth {
+            return Err(self.error("Too much nesting."));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    // skips tokens until the start of the next statement
+    fn synchronize(&mut self) {
+        while !self.check(TokenType::Eof) {
+            if matches!(self.advance().typ, TokenType::Semicolon) {
+                return;
+            }
+            if matches!(
+                self.peek().typ,
+                TokenType::Class
+                    | TokenType::Fun
+                    | TokenType::Var
+                    | TokenType::For
+                    | TokenType::If
+                    | TokenType::While
+                    | TokenType::Print
+                    | TokenType::Return
+                    | TokenType::Break
+                    | TokenType::Continue
+            ) {
+                return;
+            }
+        }
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        error_at(self.peek(), message)
+    }
+
+    fn error_previous(&self, message: &str) -> ParseError {
+        error_at(self.previous(), message)
+    }
+}
+
+// an increment or decrement by token of target, which must be assignable
+fn update(token: &Token, prefix: bool, target: Expr) -> Result<Expr, ParseError> {
+    if !matches!(target, Expr::Variable { .. } | Expr::Get { .. }) {
+        return Err(error_at(token, "Invalid assignment target."));
+    }
+    let op = match token.typ {
+        TokenType::PlusPlus => UpdateOp::Inc,
+        _ => UpdateOp::Dec,
+    };
+    Ok(Expr::Update {
+        op,
+        prefix,
+        span: token.span,
+        target: Box::new(target),
+    })
+}
+
+fn error_at(token: &Token, message: &str) -> ParseError {
+    ParseError {
+        span: token.span,
+        location: match token.typ {
+            TokenType::Eof => "end".to_string(),
+            _ => format!("'{}'", token.lexeme),
+        },
+        message: message.to_string(),
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ParseError {
+    pub span: Span,
+    pub location: String,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Error at {}: {}",
+            self.span.line, self.span.col, self.location, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scanner;
+
+    fn parse(src: &str) -> Expr {
+        Parser::new(Scanner::new(src).scan().unwrap())
+            .parse()
+            .unwrap()
+    }
+
+    fn is_number(expr: &Expr, expected: f64) -> bool {
+        matches!(expr, Expr::Literal { value: Literal::Number(n), .. } if *n == expected)
+    }
+
+    #[test]
+    fn factor_binds_tighter_than_term() {
+        let expr = parse("1 + 2 * 3");
+        let Expr::Binary {
+            left,
+            op: BinaryOp::Add,
+            right,
+            ..
+        } = &expr
+        else {
+            panic!("expected an addition, got {:?}", expr);
+        };
+        assert!(is_number(left, 1.0));
+        let Expr::Binary {
+            left,
+            op: BinaryOp::Mul,
+            right,
+            ..
+        } = &**right
+        else {
+            panic!("expected a multiplication, got {:?}", right);
+        };
+        assert!(is_number(left, 2.0));
+        assert!(is_number(right, 3.0));
+    }
+
+    #[test]
+    fn binary_operators_are_left_associative() {
+        for src in ["8 / 4 / 2", "8 * 4 * 2", "8 - 4 - 2", "8 == 4 == 2"] {
+            let expr = parse(src);
+            let Expr::Binary { left, right, .. } = &expr else {
+                panic!("expected a binary expression, got {:?}", expr);
+            };
+            assert!(is_number(right, 2.0), "{} parsed as {:?}", src, expr);
+            assert!(
+                matches!(**left, Expr::Binary { .. }),
+                "{} parsed as {:?}",
+                src,
+                expr
+            );
+        }
+    }
+
+    #[test]
+    fn missing_close_paren_is_an_error() {
+        let tokens = Scanner::new("(1 + 2").scan().unwrap();
+        let error = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(error.message, "Expect ')' after expression.");
+    }
+
+    #[test]
+    fn printer_parenthesizes_every_subexpression() {
+        let cases = [
+            ("1 + 2 * 3", "(+ 1 (* 2 3))"),
+            ("-1 * (2 + 3)", "(* (- 1) (group (+ 2 3)))"),
+            ("!true == false", "(== (! true) false)"),
+            ("\"a\" + nil", "(+ \"a\" nil)"),
+        ];
+        for (src, printed) in cases {
+            assert_eq!(parse(src).to_string(), printed, "{}", src);
+        }
+    }
+
+    fn parse_program(src: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        Parser::new(Scanner::new(src).scan().unwrap()).parse_program()
+    }
+
+    #[test]
+    fn parses_a_program_of_several_statements() {
+        let stmts = parse_program("var a = 1;\nprint a;\n{ var b; a = b; }").unwrap();
+        assert_eq!(stmts.len(), 3);
+        assert!(matches!(&stmts[0], Stmt::Var { name, initializer: Some(_), .. } if name == "a"));
+        assert!(matches!(
+            &stmts[1],
+            Stmt::Print { expr: Expr::Variable { name, .. }, .. } if name == "a"
+        ));
+        let Stmt::Block { stmts: block, .. } = &stmts[2] else {
+            panic!("expected a block, got {:?}", stmts[2]);
+        };
+        assert!(matches!(
+            &block[0],
+            Stmt::Var {
+                initializer: None,
+                ..
+            }
+        ));
+        assert!(matches!(&block[1], Stmt::Expression(Expr::Assign { .. })));
+    }
+
+    #[test]
+    fn each_malformed_statement_is_reported_once() {
+        let errors = parse_program("var = 1;\nprint 1 +;\nprint 2;\nvar x 3;").unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|error| error.span.line).collect();
+        assert_eq!(lines, [1, 2, 4]);
+        assert_eq!(errors[0].message, "Expect variable name.");
+    }
+
+    #[test]
+    fn ternary_needs_a_colon() {
+        let tokens = Scanner::new("true ? 1").scan().unwrap();
+        let error = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(error.message, "Expect ':' in ternary.");
+        assert_eq!(
+            parse("a ? b : c ? d : e").to_string(),
+            "(?: a b (?: c d e))"
+        );
+    }
+
+    fn nesting_error(src: &str) -> ParseError {
+        let errors = parse_program(src).unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].message, "Too much nesting.");
+        errors.into_iter().next().unwrap()
+    }
+
+    // the default limits leave room for an unoptimized build on a main thread's stack, which is
+    // bigger than a test thread's
+    fn with_main_stack(f: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_rather_than_a_crash() {
+        with_main_stack(|| {
+            let n = 5000;
+            nesting_error(&format!("print {}1{};", "(".repeat(n), ")".repeat(n)));
+            nesting_error(&format!("print {}{};", "[".repeat(n), "]".repeat(n)));
+            nesting_error(&format!("print {}1{};", "{1: ".repeat(n), "}".repeat(n)));
+            nesting_error(&format!("print {}1{};", "f(".repeat(n), ")".repeat(n)));
+            nesting_error(&format!("print {}1;", "!".repeat(n)));
+        });
+    }
+
+    #[test]
+    fn long_left_associative_chains_are_bounded() {
+        with_main_stack(|| {
+            nesting_error(&format!("print 1{};", " + 1".repeat(1000)));
+            nesting_error(&format!("print x{};", ".a".repeat(1000)));
+            nesting_error(&format!("print f{};", "()".repeat(1000)));
+            nesting_error(&format!("print 1{};", " and 1".repeat(1000)));
+            nesting_error(&format!("print \"{}\";", "${1}".repeat(1000)));
+            let error = nesting_error(&format!("print 1{};", ", 1".repeat(1000)));
+            assert_eq!(error.location, "','");
+            // the longest chain within the limit still parses and runs
+            let src = format!("print 1{};", " + 1".repeat(255));
+            assert_eq!(crate::run_to_string(&src).unwrap(), "256\n");
+            nesting_error(&format!("print 1{};", " + 1".repeat(256)));
+        });
+    }
+
+    #[test]
+    fn max_depth_is_configurable() {
+        let tokens = Scanner::new("[[1]]").scan().unwrap();
+        let error = Parser::new(tokens).with_max_depth(2).parse().unwrap_err();
+        assert_eq!(error.message, "Too much nesting.");
+        let tokens = Scanner::new("[[1]]").scan().unwrap();
+        assert!(Parser::new(tokens).with_max_depth(3).parse().is_ok());
+    }
+
+    #[test]
+    fn every_node_spans_the_source_it_was_parsed_from() {
+        let src = "print -a.b(1, [2]) * (3 + x);";
+        let stmts = parse_program(src).unwrap();
+        assert_eq!(stmts[0].span().text(src), "print -a.b(1, [2]) * (3 + x)");
+        let Stmt::Print { expr, .. } = &stmts[0] else {
+            panic!("expected a print, got {:?}", stmts[0]);
+        };
+        assert_eq!(expr.span().text(src), "-a.b(1, [2]) * (3 + x)");
+        let Expr::Binary { left, right, .. } = expr else {
+            panic!("expected a binary expression, got {:?}", expr);
+        };
+        assert_eq!(left.span().text(src), "-a.b(1, [2])");
+        assert_eq!(right.span().text(src), "(3 + x)");
+        let Expr::Unary { right: call, .. } = left.as_ref() else {
+            panic!("expected a negation, got {:?}", left);
+        };
+        let Expr::Call { span, args, .. } = call.as_ref() else {
+            panic!("expected a call, got {:?}", call);
+        };
+        assert_eq!(span.text(src), "(1, [2])");
+        assert_eq!(args[0].span().text(src), "1");
+        assert_eq!(args[1].span().text(src), "[2]");
+    }
+
+    #[test]
+    fn statements_span_from_their_keyword() {
+        let src = "for (var i = 0; i < 3; i = i + 1) print i;\nif (a) b; else { c; }";
+        let stmts = parse_program(src).unwrap();
+        assert_eq!(
+            stmts[0].span().text(src),
+            "for (var i = 0; i < 3; i = i + 1) print i"
+        );
+        assert_eq!(stmts[1].span().text(src), "if (a) b; else { c; }");
+        assert_eq!(stmts[1].span().line, 2);
+    }
+
+    #[test]
+    fn compound_assignment_needs_an_assignable_target() {
+        let errors = parse_program("3 += 1;").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Invalid assignment target.");
+        assert_eq!(errors[0].location, "'+='");
+        assert!(parse_program("var a; a.b -= 1;").is_ok());
+    }
+
+    #[test]
+    fn increment_needs_an_assignable_target() {
+        for src in ["1++;", "++(a);", "--a();"] {
+            let errors = parse_program(src).unwrap_err();
+            assert_eq!(errors[0].message, "Invalid assignment target.", "{}", src);
+        }
+    }
+
+    #[test]
+    fn match_token_advances_only_on_a_hit() {
+        let mut parser = Parser::new(Scanner::new("+ 1").scan().unwrap());
+        assert!(!parser.match_token(&[TokenType::Minus, TokenType::Star]));
+        assert_eq!(parser.curr, 0);
+        assert!(parser.match_token(&[TokenType::Minus, TokenType::Plus]));
+        assert_eq!(parser.curr, 1);
+        assert!(parser.check(TokenType::Number(1.0)));
+        assert_eq!(parser.peek_next().typ, TokenType::Eof);
+    }
+
+    #[test]
+    fn separate_syntax_errors_are_all_reported() {
+        let errors =
+            parse_program("print 1;\nvar = 2;\nprint 3;\nprint (4;\nprint 5;").unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|error| error.span.line).collect();
+        assert_eq!(lines, [2, 4]);
+        assert_eq!(errors[0].message, "Expect variable name.");
+        assert_eq!(errors[1].message, "Expect ')' after expression.");
+    }
+
+    #[test]
+    fn strict_declarations_need_an_initializer() {
+        let src = "var a = 1;\nvar b;\nvar c;";
+        let stmts = parse_program(src).unwrap();
+        assert!(matches!(
+            &stmts[1],
+            Stmt::Var {
+                initializer: None,
+                ..
+            }
+        ));
+        let errors = Parser::new(Scanner::new(src).scan().unwrap())
+            .with_strict_declarations(true)
+            .parse_program()
+            .unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|error| error.span.line).collect();
+        assert_eq!(lines, [2, 3]);
+        assert_eq!(errors[0].message, "Variable must be initialized.");
+        assert_eq!(errors[0].location, "'b'");
+    }
+}
@@ -0,0 +1,679 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use crate::{BinaryOp, Expr, ExprId, FunctionDecl, Span, Stmt};
+
+#[derive(Clone, Copy, PartialEq, Default)]
+enum FunctionKind {
+    #[default]
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+enum ClassKind {
+    #[default]
+    None,
+    Class,
+    Subclass,
+}
+
+// which variables the resolver warns about if they're never read
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum UnusedLint {
+    #[default]
+    Off,
+    // locals other than function parameters
+    Locals,
+    // every variable, including globals and parameters
+    All,
+}
+
+// a variable declared in a local scope
+struct Local {
+    // false while its initializer is being resolved
+    defined: bool,
+    // where it was declared, or None for this and super
+    span: Option<Span>,
+    param: bool,
+    read: bool,
+}
+
+impl Local {
+    // this and super, which are never reported as unused
+    fn implicit() -> Local {
+        Local {
+            defined: true,
+            span: None,
+            param: false,
+            read: false,
+        }
+    }
+}
+
+// the scope distance of each local variable reference
+type Locals = HashMap<ExprId, usize>;
+
+// computes how many scopes out each local variable reference lives
+#[derive(Default)]
+pub struct Resolver {
+    scopes: Vec<HashMap<String, Local>>,
+    locals: Locals,
+    function: FunctionKind,
+    class: ClassKind,
+    // how many loops enclose the current statement within the current function
+    loops: usize,
+    errors: Vec<ResolveError>,
+    warnings: Vec<Warning>,
+    unused: UnusedLint,
+    // globals only need tracking for UnusedLint::All, as they may be read before being declared
+    globals: Vec<(String, Span)>,
+    global_reads: HashSet<String>,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver::default()
+    }
+
+    // warns "Unused variable 'x'." about variables that are never read, which are only reported
+    // once the whole scope they're declared in has been resolved
+    pub fn with_unused_lint(mut self, unused: UnusedLint) -> Resolver {
+        self.unused = unused;
+        self
+    }
+
+    // references missing from the result are globals
+    pub fn resolve(self, stmts: &[Stmt]) -> Result<Locals, Vec<ResolveError>> {
+        self.resolve_with_warnings(stmts).0
+    }
+
+    // like resolve, also returning warnings about code that's legal but likely a mistake
+    pub fn resolve_with_warnings(
+        mut self,
+        stmts: &[Stmt],
+    ) -> (Result<Locals, Vec<ResolveError>>, Vec<Warning>) {
+        self.resolve_stmts(stmts);
+        for (name, span) in std::mem::take(&mut self.globals) {
+            if !self.global_reads.contains(&name) {
+                self.warn_unused(&name, span);
+            }
+        }
+        // unused variables are found scope by scope, so this puts them back in source order
+        self.warnings
+            .sort_by(|a, b| (a.span.start, &a.location).cmp(&(b.span.start, &b.location)));
+        let result = if self.errors.is_empty() {
+            Ok(self.locals)
+        } else {
+            Err(self.errors)
+        };
+        (result, self.warnings)
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        // whether an earlier statement always exits the block
+        let mut exited = false;
+        // only the first dead statement after it is reported
+        let mut reported = false;
+        for stmt in stmts {
+            if exited && !reported {
+                self.warnings.push(Warning {
+                    span: stmt.span(),
+                    location: None,
+                    message: "Unreachable code.".to_string(),
+                });
+                reported = true;
+            }
+            self.resolve_stmt(stmt);
+            exited = exited || exits(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print { expr, .. } => self.resolve_expr(expr),
+            Stmt::Var {
+                name,
+                span,
+                initializer,
+            } => {
+                self.declare(name, *span);
+                if let Some(expr) = initializer {
+                    self.resolve_expr(expr);
+                }
+                self.define(name);
+            }
+            Stmt::Block { stmts, .. } => {
+                self.scopes.push(HashMap::new());
+                self.resolve_stmts(stmts);
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                self.resolve_expr(condition);
+                self.loops += 1;
+                self.resolve_stmt(body);
+                self.loops -= 1;
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+            }
+            Stmt::Break { span } => {
+                if self.loops == 0 {
+                    self.error(*span, "'break'", "Can't break outside a loop.");
+                }
+            }
+            Stmt::Continue { span } => {
+                if self.loops == 0 {
+                    self.error(*span, "'continue'", "Can't continue outside a loop.");
+                }
+            }
+            Stmt::Function(decl) => {
+                // defined eagerly so the function can refer to itself
+                self.declare(&decl.name, decl.span);
+                self.define(&decl.name);
+                self.resolve_function(decl, FunctionKind::Function);
+            }
+            Stmt::Return { span, value } => {
+                if self.function == FunctionKind::None {
+                    self.error(*span, "'return'", "Can't return from top-level code.");
+                }
+                if let Some(value) = value {
+                    if self.function == FunctionKind::Initializer {
+                        self.error(
+                            *span,
+                            "'return'",
+                            "Can't return a value from an initializer.",
+                        );
+                    }
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Class {
+                name,
+                span,
+                superclass,
+                methods,
+            } => {
+                let enclosing = self.class;
+                self.class = ClassKind::Class;
+                self.declare(name, *span);
+                self.define(name);
+                if let Some(superclass) = superclass {
+                    self.class = ClassKind::Subclass;
+                    self.resolve_expr(superclass);
+                    self.scopes
+                        .push(HashMap::from([("super".to_string(), Local::implicit())]));
+                }
+                self.scopes
+                    .push(HashMap::from([("this".to_string(), Local::implicit())]));
+                for method in methods {
+                    let kind = if method.name == "init" {
+                        FunctionKind::Initializer
+                    } else {
+                        FunctionKind::Method
+                    };
+                    self.resolve_function(method, kind);
+                }
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+                self.class = enclosing;
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, decl: &FunctionDecl, kind: FunctionKind) {
+        let enclosing = self.function;
+        self.function = kind;
+        // a loop outside the function can't be broken out of from inside it
+        let loops = std::mem::take(&mut self.loops);
+        self.scopes.push(HashMap::new());
+        for (param, span) in &decl.params {
+            self.declare(param, *span);
+            self.define(param);
+            if let Some(local) = self
+                .scopes
+                .last_mut()
+                .and_then(|scope| scope.get_mut(param))
+            {
+                local.param = true;
+            }
+        }
+        self.resolve_stmts(&decl.body);
+        self.end_scope();
+        self.function = enclosing;
+        self.loops = loops;
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal { .. } => {}
+            Expr::Variable { id, name, span } => {
+                let local = self.scopes.last().and_then(|scope| scope.get(name));
+                if local.is_some_and(|local| !local.defined) {
+                    self.error(
+                        *span,
+                        &format!("'{}'", name),
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                self.resolve_local(*id, name, true);
+            }
+            Expr::Assign {
+                id, name, value, ..
+            } => {
+                self.resolve_expr(value);
+                self.resolve_local(*id, name, false);
+            }
+            Expr::Unary { right, .. } => self.resolve_expr(right),
+            Expr::Update { target, .. } => self.resolve_expr(target),
+            Expr::List { elements, .. } => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::Binary {
+                left,
+                op,
+                span,
+                right,
+            } => {
+                // a parenthesized comparison is a Grouping, so (a < b) < c is taken as meant
+                if is_comparison(*op) && [left, right].iter().any(|side| is_comparison_expr(side)) {
+                    self.warn(
+                        *span,
+                        &format!("'{}'", op),
+                        "Chained comparison does not mean what you think; use 'and'.",
+                    );
+                }
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Logical { left, right, .. } | Expr::Comma { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_expr(then_branch);
+                self.resolve_expr(else_branch);
+            }
+            Expr::Lambda(decl) => self.resolve_function(decl, FunctionKind::Function),
+            Expr::Call { callee, args, .. } => {
+                self.resolve_expr(callee);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expr(object),
+            Expr::Set { object, value, .. } | Expr::CompoundSet { object, value, .. } => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::This { id, span } => {
+                if self.class == ClassKind::None {
+                    self.error(*span, "'this'", "Can't use 'this' outside of a class.");
+                    return;
+                }
+                self.resolve_local(*id, "this", true);
+            }
+            Expr::Super { id, span, .. } => {
+                match self.class {
+                    ClassKind::None => {
+                        self.error(*span, "'super'", "Can't use 'super' outside of a class.")
+                    }
+                    ClassKind::Class => self.error(
+                        *span,
+                        "'super'",
+                        "Can't use 'super' in a class with no superclass.",
+                    ),
+                    ClassKind::Subclass => {}
+                }
+                self.resolve_local(*id, "super", true);
+            }
+            Expr::Grouping { expr, .. } => self.resolve_expr(expr),
+        }
+    }
+
+    // read is false for assignments, which don't count as using a variable
+    fn resolve_local(&mut self, id: ExprId, name: &str, read: bool) {
+        let found = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| scope.get_mut(name).map(|local| (depth, local)));
+        match found {
+            Some((depth, local)) => {
+                local.read |= read;
+                self.locals.insert(id, depth);
+            }
+            None if read => {
+                self.global_reads.insert(name.to_string());
+            }
+            None => {}
+        }
+    }
+
+    fn end_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        for (name, local) in scope {
+            let linted = match self.unused {
+                UnusedLint::Off => false,
+                UnusedLint::Locals => !local.param,
+                UnusedLint::All => true,
+            };
+            if let (Some(span), false, true) = (local.span, local.read, linted) {
+                self.warn_unused(&name, span);
+            }
+        }
+    }
+
+    fn warn_unused(&mut self, name: &str, span: Span) {
+        self.warn(
+            span,
+            &format!("'{}'", name),
+            &format!("Unused variable '{}'.", name),
+        );
+    }
+
+    fn declare(&mut self, name: &str, span: Span) {
+        let Some(scope) = self.scopes.last_mut() else {
+            if self.unused == UnusedLint::All {
+                self.globals.push((name.to_string(), span));
+            }
+            return;
+        };
+        let local = Local {
+            defined: false,
+            span: Some(span),
+            param: false,
+            read: false,
+        };
+        if scope.insert(name.to_string(), local).is_some() {
+            self.error(
+                span,
+                &format!("'{}'", name),
+                "Already a variable with this name in this scope.",
+            );
+        }
+    }
+
+    fn define(&mut self, name: &str) {
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+            local.defined = true;
+        }
+    }
+
+    fn warn(&mut self, span: Span, location: &str, message: &str) {
+        self.warnings.push(Warning {
+            span,
+            location: Some(location.to_string()),
+            message: message.to_string(),
+        });
+    }
+
+    fn error(&mut self, span: Span, location: &str, message: &str) {
+        self.errors.push(ResolveError {
+            span,
+            location: location.to_string(),
+            message: message.to_string(),
+        });
+    }
+}
+
+#[derive(Debug)]
+pub struct ResolveError {
+    pub span: Span,
+    pub location: String,
+    pub message: String,
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at {}: {}",
+            self.span.line, self.location, self.message
+        )
+    }
+}
+
+fn is_comparison(op: BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual
+    )
+}
+
+fn is_comparison_expr(expr: &Expr) -> bool {
+    matches!(expr, Expr::Binary { op, .. } if is_comparison(*op))
+}
+
+// doesn't stop the program from running, unlike a ResolveError
+#[derive(Debug)]
+pub struct Warning {
+    pub span: Span,
+    // the token it's about, if it's about one
+    pub location: Option<String>,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(
+                f,
+                "[line {}] Warning at {}: {}",
+                self.span.line, location, self.message
+            ),
+            None => write!(f, "[line {}] Warning: {}", self.span.line, self.message),
+        }
+    }
+}
+
+// whether stmt always ends in a return, break or continue. a loop could always break too, but
+// isn't counted, as a while (true) without one never ends at all
+fn exits(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return { .. } | Stmt::Break { .. } | Stmt::Continue { .. } => true,
+        Stmt::Block { stmts, .. } => stmts.iter().any(exits),
+        Stmt::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => exits(then_branch) && exits(else_branch),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_to_string, Parser, Scanner};
+
+    fn resolve(src: &str) -> (Result<Locals, Vec<ResolveError>>, Vec<Warning>) {
+        let stmts = Parser::new(Scanner::new(src).scan().unwrap())
+            .parse_program()
+            .unwrap();
+        Resolver::new().resolve_with_warnings(&stmts)
+    }
+
+    fn errors(src: &str) -> Vec<String> {
+        match resolve(src).0 {
+            Ok(_) => vec![],
+            Err(errors) => errors.into_iter().map(|error| error.message).collect(),
+        }
+    }
+
+    #[test]
+    fn local_read_in_its_own_initializer_is_an_error() {
+        assert_eq!(
+            errors("var a = 1; { var a = a; }"),
+            ["Can't read local variable in its own initializer."]
+        );
+        assert_eq!(errors("var a = 1; var a = a;"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn redeclaring_a_local_is_an_error() {
+        assert_eq!(
+            errors("{ var a = 1; var a = 2; }"),
+            ["Already a variable with this name in this scope."]
+        );
+    }
+
+    #[test]
+    fn closure_keeps_the_binding_it_resolved_to() {
+        let src = "var a = \"global\";
+            {
+                fun show() { print a; }
+                show();
+                var a = \"block\";
+                show();
+            }";
+        assert_eq!(run_to_string(src).unwrap(), "global\nglobal\n");
+    }
+
+    fn warnings(src: &str) -> Vec<String> {
+        let (_, warnings) = resolve(src);
+        warnings
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect()
+    }
+
+    #[test]
+    fn chained_comparison_is_warned_about() {
+        assert_eq!(
+            warnings("var x = 5; print 1 < x < 10;"),
+            ["Chained comparison does not mean what you think; use 'and'."]
+        );
+        assert!(warnings("var x = 5; print 1 < x and x < 10;").is_empty());
+        assert!(warnings("var x = 5; print (1 < x) == true;").is_empty());
+    }
+
+    // the unused-variable warnings as (line, message)
+    fn unused(src: &str, lint: UnusedLint) -> Vec<(usize, String)> {
+        let stmts = Parser::new(Scanner::new(src).scan().unwrap())
+            .parse_program()
+            .unwrap();
+        let (_, warnings) = Resolver::new()
+            .with_unused_lint(lint)
+            .resolve_with_warnings(&stmts);
+        warnings
+            .into_iter()
+            .map(|warning| (warning.span.line, warning.message))
+            .collect()
+    }
+
+    #[test]
+    fn unused_local_is_warned_about_at_its_declaration() {
+        let src = "{\n  var used = 1;\n  var unused = 2;\n  print used;\n}";
+        assert_eq!(
+            unused(src, UnusedLint::Locals),
+            [(3, "Unused variable 'unused'.".to_string())]
+        );
+        assert!(unused(src, UnusedLint::Off).is_empty());
+    }
+
+    #[test]
+    fn parameters_and_globals_are_only_linted_with_all() {
+        let src = "var g = 1;\nfun f(a) {\n  return 1;\n}\nf(2);";
+        assert!(unused(src, UnusedLint::Locals).is_empty());
+        assert_eq!(
+            unused(src, UnusedLint::All),
+            [
+                (1, "Unused variable 'g'.".to_string()),
+                (2, "Unused variable 'a'.".to_string())
+            ]
+        );
+        // a parameter is underlined itself, not the name of its function
+        let stmts = Parser::new(Scanner::new(src).scan().unwrap())
+            .parse_program()
+            .unwrap();
+        let (_, warnings) = Resolver::new()
+            .with_unused_lint(UnusedLint::All)
+            .resolve_with_warnings(&stmts);
+        let span = warnings[1].span;
+        assert_eq!((span.start, span.end), (17, 18));
+        assert_eq!(span.text(src), "a");
+    }
+
+    // the unreachable-code warnings as the source they underline
+    fn unreachable(src: &str) -> Vec<&str> {
+        resolve(src)
+            .1
+            .into_iter()
+            .filter(|warning| warning.message == "Unreachable code.")
+            .map(|warning| &src[warning.span.start..warning.span.end])
+            .collect()
+    }
+
+    #[test]
+    fn code_after_return_is_unreachable() {
+        let src = "fun f() {\n  return 1;\n  print 2;\n  print 3;\n}";
+        assert_eq!(unreachable(src), ["print 2"]);
+        let src = "while (true) { { break; } var x = 1; }";
+        // a declaration is underlined from its name
+        assert_eq!(unreachable(src), ["x = 1"]);
+        let src = "fun f(a) { if (a) return 1; else { return 2; } a = 3; }";
+        assert_eq!(unreachable(src), ["a = 3"]);
+    }
+
+    #[test]
+    fn return_in_one_branch_leaves_the_rest_reachable() {
+        let src = "fun f(a) {\n  if (a) return 1;\n  print 2;\n  return 3;\n}";
+        assert!(unreachable(src).is_empty());
+        assert!(unreachable("while (true) { if (false) continue; print 1; }").is_empty());
+    }
+}
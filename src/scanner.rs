@@ -0,0 +1,1755 @@
+use std::fmt::Display;
+
+use crate::Span;
+
+pub struct Scanner<'src> {
+    src: &'src str,
+    bytes: &'src [u8],
+    start: usize,
+    curr: usize,
+    line: usize,
+    line_start: usize,
+    // the 1-based column of curr, counted in chars rather than bytes
+    col: usize,
+    // where the current token starts, whose line differs from line for multi-line strings
+    start_line: usize,
+    start_col: usize,
+    errors: Vec<ScanError>,
+    emitted_eof: bool,
+    config: ScannerConfig,
+    // for each string interpolation being scanned, innermost last, its quote and how many braces
+    // are open in its expression
+    interpolations: Vec<(u8, usize)>,
+}
+
+// which extensions to reference Lox the scanner accepts; the default enables those that don't
+// change the meaning of a reference Lox program, apart from ${ in strings
+#[derive(Debug, Clone)]
+pub struct ScannerConfig {
+    pub block_comments: bool,
+    pub scientific_notation: bool,
+    // whether break and continue are keywords rather than identifiers
+    pub extension_keywords: bool,
+    // whether strings can also be delimited by ', so "..." can appear in them unescaped
+    pub single_quote_strings: bool,
+    // whether "a${b}c" scans as Interp("a"), the tokens of b, then Str("c"), where $ can be
+    // escaped to keep ${ literal
+    pub interpolation: bool,
+    // whether .5 scans as 0.5 rather than a dot and 5; off by default as in the book. when on,
+    // a property access still needs an identifier after the dot, so a.b is unaffected, but a.5
+    // scans as a followed by 0.5
+    pub leading_dot_numbers: bool,
+    // whether literals without a fraction or exponent scan as Int rather than Number
+    pub integers: bool,
+    // whether IF and If are the keyword if too; identifiers are always case-sensitive
+    pub case_insensitive_keywords: bool,
+    // how many columns apart tab stops are, so a tab moves the column to just past the next one
+    pub tab_width: usize,
+    pub max_comment_depth: usize,
+}
+
+impl ScannerConfig {
+    // the dialect from the book, with every extension off
+    pub fn strict() -> ScannerConfig {
+        ScannerConfig {
+            block_comments: false,
+            scientific_notation: false,
+            extension_keywords: false,
+            single_quote_strings: false,
+            interpolation: false,
+            ..ScannerConfig::default()
+        }
+    }
+}
+
+impl Default for ScannerConfig {
+    fn default() -> ScannerConfig {
+        ScannerConfig {
+            block_comments: true,
+            scientific_notation: true,
+            extension_keywords: true,
+            single_quote_strings: true,
+            interpolation: true,
+            leading_dot_numbers: false,
+            integers: false,
+            case_insensitive_keywords: false,
+            tab_width: 1,
+            max_comment_depth: 64,
+        }
+    }
+}
+
+impl<'src> Scanner<'src> {
+    pub fn new(src: &str) -> Scanner<'_> {
+        Scanner::with_config(src, ScannerConfig::default())
+    }
+
+    pub fn with_config(src: &str, config: ScannerConfig) -> Scanner<'_> {
+        let mut scanner = Scanner {
+            src,
+            bytes: src.as_bytes(),
+            start: 0,
+            curr: 0,
+            line: 1,
+            line_start: 0,
+            col: 1,
+            start_line: 1,
+            start_col: 1,
+            errors: vec![],
+            emitted_eof: false,
+            config,
+            interpolations: vec![],
+        };
+        // skip a byte-order mark, keeping spans as offsets into src but starting columns after it
+        if src.starts_with('\u{feff}') {
+            scanner.curr = '\u{feff}'.len_utf8();
+            scanner.line_start = scanner.curr;
+        }
+        // skip a shebang line so scripts can be made executable; its line break is scanned as usual
+        if src[scanner.curr..].starts_with("#!") {
+            let end = src[scanner.curr..]
+                .find(['\n', '\r'])
+                .map_or(src.len(), |i| scanner.curr + i);
+            scanner.skip_to(end);
+        }
+        scanner
+    }
+
+    pub fn with_max_comment_depth(mut self, max_comment_depth: usize) -> Scanner<'src> {
+        self.config.max_comment_depth = max_comment_depth;
+        self
+    }
+
+    pub fn scan(self) -> Result<Vec<Token<'src>>, Vec<ScanError>> {
+        let ScanResult { tokens, errors } = self.scan_all();
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    // unlike scan, keeps the tokens around even when there are errors, for tools that must
+    // cope with broken code
+    pub fn scan_all(mut self) -> ScanResult<'src> {
+        let tokens = self.by_ref().collect();
+        let mut errors = self.errors;
+        errors.sort_by_key(|error| error.span.start);
+        ScanResult { tokens, errors }
+    }
+
+    // how many tokens src scans to, including Eof, without collecting them; tokens are counted
+    // the same whether or not there are errors
+    pub fn count_tokens(src: &str) -> usize {
+        Scanner::new(src).count()
+    }
+
+    // parses s, ignoring surrounding whitespace, as a single number literal with an optional
+    // leading '-', so conversions at runtime accept exactly what the scanner does
+    pub fn parse_number(s: &str) -> Option<f64> {
+        let s = s.trim();
+        let (sign, digits) = match s.strip_prefix('-') {
+            Some(digits) => (-1.0, digits),
+            None => (1.0, s),
+        };
+        let result = Scanner::new(digits).scan_all();
+        match result.tokens.as_slice() {
+            [token, eof] if result.errors.is_empty() && token.lexeme == digits => match token.typ {
+                TokenType::Number(n) if eof.typ == TokenType::Eof => Some(sign * n),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // scans new_src, an edit of the source prev was scanned from with the same config that leaves
+    // everything before edit_offset unchanged, reusing the tokens the edit can't affect. only
+    // errors from the restart point on are returned, so callers should keep any earlier ones
+    // from the previous scan
+    pub fn rescan_from<'a>(
+        prev: &[Token],
+        edit_offset: usize,
+        new_src: &'a str,
+        config: ScannerConfig,
+    ) -> ScanResult<'a> {
+        let mut reused = 0;
+        let mut interpolations = 0;
+        for (i, token) in prev.iter().enumerate() {
+            // scanning a token looks up to two bytes past its end, as 1 does to see whether
+            // .5 follows, so a token that close to the edit is scanned again
+            if token.typ == TokenType::Eof || token.span.end + 2 > edit_offset {
+                break;
+            }
+            // a fragment starting with } continues an interpolation rather than opening one
+            match token.typ {
+                TokenType::Interp(_) if !token.lexeme.starts_with('}') => interpolations += 1,
+                TokenType::Str(_) if token.lexeme.starts_with('}') => interpolations -= 1,
+                _ => {}
+            }
+            // the scanner can only restart outside an interpolation, since it doesn't know which
+            // braces are open inside one
+            if interpolations == 0 {
+                reused = i + 1;
+            }
+        }
+        let mut scanner = Scanner::with_config(new_src, config);
+        let mut tokens = vec![];
+        if let Some(last) = prev[..reused].last() {
+            tokens.extend(prev[..reused].iter().map(|token| Token {
+                typ: token.typ.clone(),
+                lexeme: &new_src[token.span.start..token.span.end],
+                span: token.span,
+            }));
+            scanner.skip_to(last.span.end);
+        }
+        let rest = scanner.scan_all();
+        tokens.extend(rest.tokens);
+        ScanResult {
+            tokens,
+            errors: rest.errors,
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token<'src>> {
+        if self.emitted_eof {
+            return None;
+        }
+        loop {
+            self.start = self.curr;
+            self.start_line = self.line;
+            self.start_col = self.col;
+            let Some(b) = self.advance() else {
+                self.emitted_eof = true;
+                return Some(self.make_token(TokenType::Eof));
+            };
+            let token = match b {
+                b'(' => Some(self.make_token(TokenType::LParen)),
+                b')' => Some(self.make_token(TokenType::RParen)),
+                b'{' => {
+                    if let Some((_, depth)) = self.interpolations.last_mut() {
+                        *depth += 1;
+                    }
+                    Some(self.make_token(TokenType::LBrace))
+                }
+                // closes an interpolated expression, so the rest of its string follows
+                b'}' if matches!(self.interpolations.last(), Some((_, 0))) => {
+                    let (quote, _) = self.interpolations.pop().unwrap();
+                    self.scan_str(quote)
+                }
+                b'}' => {
+                    if let Some((_, depth)) = self.interpolations.last_mut() {
+                        *depth -= 1;
+                    }
+                    Some(self.make_token(TokenType::RBrace))
+                }
+                b'[' => Some(self.make_token(TokenType::LBracket)),
+                b']' => Some(self.make_token(TokenType::RBracket)),
+                b',' => Some(self.make_token(TokenType::Comma)),
+                b'.' if self.config.leading_dot_numbers
+                    && self.peek().is_some_and(|c| c.is_ascii_digit()) =>
+                {
+                    self.scan_num()
+                }
+                b'.' => Some(self.make_token(TokenType::Dot)),
+                b'-' => {
+                    let typ = if self.advance_if_match(b'=') {
+                        TokenType::MinusEqual
+                    } else if self.advance_if_match(b'-') {
+                        TokenType::MinusMinus
+                    } else {
+                        TokenType::Minus
+                    };
+                    Some(self.make_token(typ))
+                }
+                b'+' => {
+                    let typ = if self.advance_if_match(b'=') {
+                        TokenType::PlusEqual
+                    } else if self.advance_if_match(b'+') {
+                        TokenType::PlusPlus
+                    } else {
+                        TokenType::Plus
+                    };
+                    Some(self.make_token(typ))
+                }
+                b';' => Some(self.make_token(TokenType::Semicolon)),
+                b'*' => {
+                    let typ = if self.advance_if_match(b'=') {
+                        TokenType::StarEqual
+                    } else if self.advance_if_match(b'*') {
+                        TokenType::StarStar
+                    } else {
+                        TokenType::Star
+                    };
+                    Some(self.make_token(typ))
+                }
+                b'%' => {
+                    let typ = if self.advance_if_match(b'=') {
+                        TokenType::PercentEqual
+                    } else {
+                        TokenType::Percent
+                    };
+                    Some(self.make_token(typ))
+                }
+                b'?' => Some(self.make_token(TokenType::Question)),
+                b':' => Some(self.make_token(TokenType::Colon)),
+                b'&' => Some(self.make_token(TokenType::Amp)),
+                b'|' => Some(self.make_token(TokenType::Pipe)),
+                b'^' => Some(self.make_token(TokenType::Caret)),
+                b'~' => Some(self.make_token(TokenType::Tilde)),
+                b'!' => {
+                    let typ = if self.advance_if_match(b'=') {
+                        TokenType::BangEqual
+                    } else {
+                        TokenType::Bang
+                    };
+                    Some(self.make_token(typ))
+                }
+                b'=' => {
+                    let typ = if self.advance_if_match(b'=') {
+                        TokenType::EqualEqual
+                    } else {
+                        TokenType::Equal
+                    };
+                    Some(self.make_token(typ))
+                }
+                b'<' => {
+                    let typ = if self.advance_if_match(b'=') {
+                        TokenType::LessEqual
+                    } else if self.advance_if_match(b'<') {
+                        TokenType::LessLess
+                    } else {
+                        TokenType::Less
+                    };
+                    Some(self.make_token(typ))
+                }
+                b'>' => {
+                    let typ = if self.advance_if_match(b'=') {
+                        TokenType::GreaterEqual
+                    } else if self.advance_if_match(b'>') {
+                        TokenType::GreaterGreater
+                    } else {
+                        TokenType::Greater
+                    };
+                    Some(self.make_token(typ))
+                }
+                b'/' => {
+                    // is comment
+                    if self.advance_if_match(b'/') {
+                        while matches!(self.peek(), Some(c) if c != b'\n' && c != b'\r') {
+                            self.advance();
+                        }
+                        None
+                    } else if self.config.block_comments && self.advance_if_match(b'*') {
+                        self.scan_block_comment();
+                        None
+                    } else if self.advance_if_match(b'=') {
+                        Some(self.make_token(TokenType::SlashEqual))
+                    } else {
+                        Some(self.make_token(TokenType::Slash))
+                    }
+                }
+                b if self.is_line_break(b) => {
+                    self.newline();
+                    None
+                }
+                b' ' | b'\r' | b'\t' => None,
+                b'"' => self.scan_str(b'"'),
+                b'\'' if self.config.single_quote_strings => self.scan_str(b'\''),
+                b if b.is_ascii_digit() => self.scan_num(),
+                b if b.is_ascii_alphabetic() || b == b'_' => Some(self.scan_ident()),
+                _ => {
+                    // collapse a run of garbage into a single error
+                    while self.peek().is_some_and(|c| !can_start_token(c)) {
+                        self.advance();
+                    }
+                    // the run ends before an ascii byte or at the end, so it's whole chars
+                    let text: String = self.src[self.start..self.curr].escape_debug().collect();
+                    if self.src[self.start..self.curr].chars().count() == 1 {
+                        self.error_at(self.start, &format!("Unexpected character '{}'.", text));
+                    } else {
+                        let (first, last) = (self.start_col, self.col - 1);
+                        self.push_error(
+                            self.token_span(),
+                            &format!(
+                                "Unexpected characters '{}' in columns {}-{}.",
+                                text, first, last
+                            ),
+                        );
+                    }
+                    None
+                }
+            };
+            if token.is_some() {
+                return token;
+            }
+        }
+    }
+
+    fn scan_block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                None => {
+                    self.push_error(self.token_span(), "Unterminated block comment.");
+                    break;
+                }
+                Some(b) if self.is_line_break(b) => self.newline(),
+                Some(b'/') if self.advance_if_match(b'*') => {
+                    depth += 1;
+                    // reported once, but the comment is still skipped to its real end
+                    if depth == self.config.max_comment_depth + 1 {
+                        self.error_at(self.curr - 2, "Too much nesting.");
+                    }
+                }
+                Some(b'*') if self.advance_if_match(b'/') => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    // quote is the delimiter the string was opened with, and the only one that closes it
+    fn scan_str<'a>(&'a mut self, quote: u8) -> Option<Token<'src>> {
+        let mut value = String::new();
+        let mut seg_start = self.curr;
+        loop {
+            match self.advance() {
+                None => {
+                    self.unterminated_str();
+                    break None;
+                }
+                Some(b) if self.is_line_break(b) => self.newline(),
+                Some(b) if b == quote => {
+                    value.push_str(&self.src[seg_start..self.curr - 1]);
+                    break Some(self.make_token(TokenType::Str(value)));
+                }
+                Some(b'$') if self.config.interpolation && self.peek() == Some(b'{') => {
+                    value.push_str(&self.src[seg_start..self.curr - 1]);
+                    self.advance();
+                    self.interpolations.push((quote, 0));
+                    break Some(self.make_token(TokenType::Interp(value)));
+                }
+                Some(b'\\') => {
+                    value.push_str(&self.src[seg_start..self.curr - 1]);
+                    let col = self.col;
+                    let c = match self.advance() {
+                        Some(b'n') => '\n',
+                        Some(b't') => '\t',
+                        Some(b'r') => '\r',
+                        Some(b'\\') => '\\',
+                        Some(b'"') => '"',
+                        Some(b'\'') => '\'',
+                        Some(b'$') => '$',
+                        Some(b'0') => '\0',
+                        Some(b'u') => match self.scan_unicode_escape() {
+                            Some(c) => c,
+                            None => {
+                                seg_start = self.curr;
+                                continue;
+                            }
+                        },
+                        Some(_) => {
+                            self.error("Invalid escape sequence.");
+                            // rescan the escaped character as ordinary string content
+                            self.curr -= 1;
+                            self.col = col;
+                            seg_start = self.curr;
+                            continue;
+                        }
+                        None => {
+                            self.unterminated_str();
+                            break None;
+                        }
+                    };
+                    value.push(c);
+                    seg_start = self.curr;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn scan_unicode_escape(&mut self) -> Option<char> {
+        if !self.advance_if_match(b'{') {
+            self.error("Expect '{' after '\\u'.");
+            return None;
+        }
+        let digits_start = self.curr;
+        while matches!(self.peek(), Some(c) if c.is_ascii_hexdigit()) {
+            self.advance();
+        }
+        let digits = &self.src[digits_start..self.curr];
+        if !self.advance_if_match(b'}') {
+            if matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric()) {
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric()) {
+                    self.advance();
+                }
+                self.advance_if_match(b'}');
+                self.error("Invalid hex digit in unicode escape.");
+            } else {
+                self.error("Expect '}' after unicode escape.");
+            }
+            return None;
+        }
+        if digits.is_empty() {
+            self.error("Empty unicode escape.");
+            return None;
+        }
+        let c = u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        if c.is_none() {
+            self.error("Unicode escape is not a valid scalar value.");
+        }
+        c
+    }
+
+    fn scan_num<'a>(&'a mut self) -> Option<Token<'src>> {
+        if self.bytes[self.start] == b'0' {
+            let radix = match self.peek() {
+                Some(b'x' | b'X') => Some(16),
+                Some(b'b' | b'B') => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                return self.scan_radix_num(radix);
+            }
+        }
+        // after a leading dot, these digits are already the fraction
+        self.scan_digits();
+        let mut integral = self.bytes[self.start] != b'.';
+        if integral
+            && self.peek() == Some(b'.')
+            && self.peek_next().is_some_and(|b| b.is_ascii_digit())
+        {
+            integral = false;
+            self.advance();
+            self.scan_digits();
+        }
+        if self.config.scientific_notation && matches!(self.peek(), Some(b'e' | b'E')) {
+            integral = false;
+            self.advance();
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.advance();
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.error("Malformed exponent.");
+                return None;
+            }
+            self.scan_digits();
+        }
+        let digits = self.src[self.start..self.curr].replace('_', "");
+        if integral && self.config.integers {
+            let Ok(n) = digits.parse() else {
+                self.push_error(self.token_span(), "Integer literal out of range.");
+                return None;
+            };
+            return Some(self.make_token(TokenType::Int(n)));
+        }
+        // every run of digits, with an optional fraction and exponent, parses as an f64
+        let value: f64 = digits.parse().expect("scanned digits are a valid float");
+        self.number(value)
+    }
+
+    // consumes a run of decimal digits, allowing single '_' separators between them
+    fn scan_digits(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_ascii_digit() => {
+                    self.advance();
+                }
+                Some(b'_') => {
+                    let mut underscores = 0;
+                    while self.advance_if_match(b'_') {
+                        underscores += 1;
+                    }
+                    if underscores > 1 {
+                        self.error("Repeated '_' in number literal.");
+                    } else if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                        self.error("Trailing '_' in number literal.");
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn scan_radix_num<'a>(&'a mut self, radix: u32) -> Option<Token<'src>> {
+        let digits_start = self.curr;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric()) {
+            self.advance();
+        }
+        let digits = &self.src[digits_start..self.curr];
+        if digits.is_empty() {
+            self.error("Expect digits after number prefix.");
+            return None;
+        }
+        let mut value = 0.0;
+        let mut int = Some(0i64);
+        for c in digits.chars() {
+            match c.to_digit(radix) {
+                Some(d) => {
+                    value = value * radix as f64 + d as f64;
+                    int = int
+                        .and_then(|n| n.checked_mul(radix as i64))
+                        .and_then(|n| n.checked_add(d as i64));
+                }
+                None => {
+                    self.error(&format!("Invalid digit '{}' in number literal.", c));
+                    return None;
+                }
+            }
+        }
+        if self.config.integers {
+            let Some(n) = int else {
+                self.push_error(self.token_span(), "Integer literal out of range.");
+                return None;
+            };
+            return Some(self.make_token(TokenType::Int(n)));
+        }
+        self.number(value)
+    }
+
+    // literals too big for an f64 parse to infinity, which is reported rather than kept
+    fn number(&mut self, value: f64) -> Option<Token<'src>> {
+        if value.is_infinite() {
+            self.push_error(self.token_span(), "Number literal out of range.");
+            return None;
+        }
+        Some(self.make_token(TokenType::Number(value)))
+    }
+
+    fn scan_ident<'a>(&'a mut self) -> Token<'src> {
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_') {
+            self.advance();
+        }
+        let text = &self.src[self.start..self.curr];
+        let keyword = if self.config.case_insensitive_keywords {
+            keyword(&text.to_ascii_lowercase())
+        } else {
+            keyword(text)
+        };
+        let typ = match keyword {
+            Some(TokenType::Break | TokenType::Continue) if !self.config.extension_keywords => {
+                TokenType::Ident
+            }
+            Some(typ) => typ,
+            None => TokenType::Ident,
+        };
+        self.make_token(typ)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.curr).copied()
+    }
+
+    fn peek_next(&self) -> Option<u8> {
+        self.bytes.get(self.curr + 1).copied()
+    }
+
+    fn advance(&mut self) -> Option<u8> {
+        let b = *self.bytes.get(self.curr)?;
+        self.curr += 1;
+        self.col = self.next_col(self.col, b);
+        Some(b)
+    }
+
+    fn advance_if_match(&mut self, expected: u8) -> bool {
+        if self.peek() == Some(expected) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    // consumes everything up to offset, counting the lines it passes
+    fn skip_to(&mut self, offset: usize) {
+        while self.curr < offset {
+            match self.advance() {
+                Some(b) if self.is_line_break(b) => self.newline(),
+                _ => {}
+            }
+        }
+    }
+
+    // whether the just-consumed b ends a line; the '\r' of a "\r\n" pair leaves it to the '\n'
+    fn is_line_break(&self, b: u8) -> bool {
+        b == b'\n' || (b == b'\r' && self.peek() != Some(b'\n'))
+    }
+
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.curr;
+        self.col = 1;
+    }
+
+    fn error(&mut self, message: &str) {
+        self.error_at(self.curr.saturating_sub(1), message);
+    }
+
+    // reported where the string started, since that's where the missing quote belongs
+    fn unterminated_str(&mut self) {
+        let message = if self.start_line == self.line {
+            "Unterminated string.".to_string()
+        } else {
+            format!(
+                "Unterminated string (reached end of input on line {}).",
+                self.line
+            )
+        };
+        self.push_error(self.token_span(), &message);
+    }
+
+    // an error covering the char at offset
+    fn error_at(&mut self, offset: usize, message: &str) {
+        let end = (offset + 1..=self.src.len())
+            .find(|&i| self.src.is_char_boundary(i))
+            .unwrap_or(offset);
+        let span = Span {
+            start: offset,
+            end,
+            line: self.line,
+            col: self.column(offset),
+        };
+        self.push_error(span, message);
+    }
+
+    fn push_error(&mut self, span: Span, message: &str) {
+        self.errors.push(ScanError {
+            span,
+            message: message.to_string(),
+        });
+    }
+
+    // the span of everything consumed since the current token started
+    fn token_span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.curr,
+            line: self.start_line,
+            col: self.start_col,
+        }
+    }
+
+    // the column after b, if b is consumed at col
+    fn next_col(&self, col: usize, b: u8) -> usize {
+        let tab_width = self.config.tab_width.max(1);
+        match b {
+            b'\t' => (col - 1) / tab_width * tab_width + tab_width + 1,
+            // utf-8 continuation bytes don't start a new char
+            _ if b & 0xc0 == 0x80 => col,
+            _ => col + 1,
+        }
+    }
+
+    // the column of offset, which is on the current line and in the current token, so only the
+    // token is walked rather than the whole line
+    fn column(&self, offset: usize) -> usize {
+        let (from, col) = if self.start_line == self.line {
+            (self.start, self.start_col)
+        } else {
+            (self.line_start, 1)
+        };
+        self.bytes[from..offset]
+            .iter()
+            .fold(col, |col, &b| self.next_col(col, b))
+    }
+
+    fn make_token<'a>(&'a self, typ: TokenType) -> Token<'src> {
+        Token {
+            typ,
+            lexeme: &self.src[self.start..self.curr],
+            span: self.token_span(),
+        }
+    }
+}
+
+// source that scans back to the same tokens, with a space between two only where they'd otherwise
+// run together. strings are requoted with " and escaped, so a formatter can build on this
+pub fn tokens_to_source(tokens: &[Token]) -> String {
+    let mut src = String::new();
+    for token in tokens {
+        let text = match &token.typ {
+            TokenType::Eof => continue,
+            // a fragment starting with } continues an interpolation rather than opening one
+            TokenType::Str(s) | TokenType::Interp(s) => {
+                let open = if token.lexeme.starts_with('}') {
+                    "}"
+                } else {
+                    "\""
+                };
+                let close = if matches!(token.typ, TokenType::Str(_)) {
+                    "\""
+                } else {
+                    "${"
+                };
+                format!("{}{}{}", open, escape_str(s), close)
+            }
+            _ => token.lexeme.to_string(),
+        };
+        if let (Some(a), Some(b)) = (src.chars().last(), text.chars().next()) {
+            if runs_together(a, b) {
+                src.push(' ');
+            }
+        }
+        src.push_str(&text);
+    }
+    src
+}
+
+fn runs_together(a: char, b: char) -> bool {
+    let word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    (word(a) && word(b))
+        || (a.is_ascii_digit() && b == '.')
+        || (a == '.' && b.is_ascii_digit())
+        || matches!(
+            (a, b),
+            ('=' | '!' | '<' | '>' | '+' | '-' | '*' | '/' | '%', '=')
+                | ('<', '<')
+                | ('>', '>')
+                | ('+', '+')
+                | ('*', '*')
+                | ('-', '-')
+                | ('/', '/' | '*')
+        )
+}
+
+pub(crate) fn escape_str(s: &str) -> String {
+    let mut escaped = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '$' if chars.peek() == Some(&'{') => escaped.push_str("\\$"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn can_start_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"(){}[],.-+;*%?:&|^~!=<>/ \r\t\n\"'_".contains(&b)
+}
+
+fn keyword(ident: &str) -> Option<TokenType> {
+    let typ = match ident {
+        "and" => TokenType::And,
+        "break" => TokenType::Break,
+        "class" => TokenType::Class,
+        "continue" => TokenType::Continue,
+        "else" => TokenType::Else,
+        "false" => TokenType::False,
+        "for" => TokenType::For,
+        "fun" => TokenType::Fun,
+        "if" => TokenType::If,
+        "nil" => TokenType::Nil,
+        "or" => TokenType::Or,
+        "print" => TokenType::Print,
+        "return" => TokenType::Return,
+        "super" => TokenType::Super,
+        "this" => TokenType::This,
+        "true" => TokenType::True,
+        "var" => TokenType::Var,
+        "while" => TokenType::While,
+        _ => return None,
+    };
+    Some(typ)
+}
+
+impl<'src> Iterator for Scanner<'src> {
+    type Item = Token<'src>;
+
+    fn next(&mut self) -> Option<Token<'src>> {
+        self.next_token()
+    }
+}
+
+// the tokens end with Eof whether or not there were errors, which are ordered by position
+#[derive(Debug)]
+pub struct ScanResult<'src> {
+    pub tokens: Vec<Token<'src>>,
+    pub errors: Vec<ScanError>,
+}
+
+// serializes as {"type", "value", "lexeme", "span"}, with a value only for a literal
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Token<'src> {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub typ: TokenType,
+    pub lexeme: &'src str,
+    pub span: Span,
+}
+
+// e.g. Number(3) "3" @ line 1
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:?} @ line {}",
+            self.typ, self.lexeme, self.span.line
+        )
+    }
+}
+
+// equality on Number compares the f64s, so a NaN token is never equal to anything
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "type", content = "value")
+)]
+pub enum TokenType {
+    // Single-character tokens.
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    Dot,
+    Minus,
+    Plus,
+    Semicolon,
+    Slash,
+    Star,
+    Percent,
+    Question,
+    Colon,
+    Amp,
+    Pipe,
+    Caret,
+    Tilde,
+
+    // One or two character tokens.
+    Bang,
+    BangEqual,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    StarStar,
+    SlashEqual,
+    PercentEqual,
+    PlusPlus,
+    MinusMinus,
+    Equal,
+    EqualEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    LessLess,
+    GreaterGreater,
+
+    // Literals.
+    Ident,
+    Str(String),
+    // a string fragment ending in ${, followed by the tokens of an expression
+    Interp(String),
+    Number(f64),
+    // only with ScannerConfig::integers
+    Int(i64),
+
+    // Keywords.
+    And,
+    Break,
+    Class,
+    Continue,
+    Else,
+    False,
+    Fun,
+    For,
+    If,
+    Nil,
+    Or,
+    Print,
+    Return,
+    Super,
+    This,
+    True,
+    Var,
+    While,
+
+    Eof,
+}
+
+// punctuation and keywords print as written; literals show their value
+impl Display for TokenType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            TokenType::LParen => "(",
+            TokenType::RParen => ")",
+            TokenType::LBrace => "{",
+            TokenType::RBrace => "}",
+            TokenType::LBracket => "[",
+            TokenType::RBracket => "]",
+            TokenType::Comma => ",",
+            TokenType::Dot => ".",
+            TokenType::Minus => "-",
+            TokenType::Plus => "+",
+            TokenType::Semicolon => ";",
+            TokenType::Slash => "/",
+            TokenType::Star => "*",
+            TokenType::Percent => "%",
+            TokenType::Question => "?",
+            TokenType::Colon => ":",
+            TokenType::Amp => "&",
+            TokenType::Pipe => "|",
+            TokenType::Caret => "^",
+            TokenType::Tilde => "~",
+            TokenType::Bang => "!",
+            TokenType::BangEqual => "!=",
+            TokenType::PlusEqual => "+=",
+            TokenType::MinusEqual => "-=",
+            TokenType::StarEqual => "*=",
+            TokenType::StarStar => "**",
+            TokenType::SlashEqual => "/=",
+            TokenType::PercentEqual => "%=",
+            TokenType::PlusPlus => "++",
+            TokenType::MinusMinus => "--",
+            TokenType::Equal => "=",
+            TokenType::EqualEqual => "==",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::LessLess => "<<",
+            TokenType::GreaterGreater => ">>",
+            TokenType::Ident => "identifier",
+            TokenType::Str(s) => return write!(f, "Str({:?})", s),
+            TokenType::Interp(s) => return write!(f, "Interp({:?})", s),
+            TokenType::Number(n) => return write!(f, "Number({})", n),
+            TokenType::Int(n) => return write!(f, "Int({})", n),
+            TokenType::And => "and",
+            TokenType::Break => "break",
+            TokenType::Class => "class",
+            TokenType::Continue => "continue",
+            TokenType::Else => "else",
+            TokenType::False => "false",
+            TokenType::Fun => "fun",
+            TokenType::For => "for",
+            TokenType::If => "if",
+            TokenType::Nil => "nil",
+            TokenType::Or => "or",
+            TokenType::Print => "print",
+            TokenType::Return => "return",
+            TokenType::Super => "super",
+            TokenType::This => "this",
+            TokenType::True => "true",
+            TokenType::Var => "var",
+            TokenType::While => "while",
+            TokenType::Eof => "end of file",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ScanError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Error: {}",
+            self.span.line, self.span.col, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(src: &str) -> Vec<TokenType> {
+        Scanner::new(src)
+            .scan()
+            .unwrap()
+            .into_iter()
+            .map(|token| token.typ)
+            .collect()
+    }
+
+    #[test]
+    fn line_comment_is_skipped_to_end_of_line() {
+        let tokens = Scanner::new("var x = 1; // hello\nvar y = 2;")
+            .scan()
+            .unwrap();
+        let lexemes: Vec<_> = tokens.iter().map(|token| token.lexeme).collect();
+        assert_eq!(
+            lexemes,
+            ["var", "x", "=", "1", ";", "var", "y", "=", "2", ";", ""]
+        );
+        assert_eq!(tokens[5].span.line, 2);
+    }
+
+    #[test]
+    fn keyword_prefix_is_still_an_identifier() {
+        assert_eq!(types("orchid"), [TokenType::Ident, TokenType::Eof]);
+        assert_eq!(types("or"), [TokenType::Or, TokenType::Eof]);
+        assert_eq!(
+            types("var _x1 = nil;"),
+            [
+                TokenType::Var,
+                TokenType::Ident,
+                TokenType::Equal,
+                TokenType::Nil,
+                TokenType::Semicolon,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_eq!(
+            types("1 /* a /* b */ c */ 2"),
+            [
+                TokenType::Number(1.0),
+                TokenType::Number(2.0),
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_an_error() {
+        let errors = Scanner::new("1 /* a /* b */").scan().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Unterminated block comment.");
+    }
+
+    #[test]
+    fn multi_line_block_comment_counts_lines() {
+        let tokens = Scanner::new("/* a\nb\n*/ x").scan().unwrap();
+        assert_eq!(tokens[0].typ, TokenType::Ident);
+        assert_eq!(tokens[0].span.line, 3);
+    }
+
+    #[test]
+    fn columns_restart_on_each_line() {
+        let tokens = Scanner::new("a\n  bc d\n\tefg").scan().unwrap();
+        let positions: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.span.line, token.span.col))
+            .collect();
+        assert_eq!(positions, [(1, 1), (2, 3), (2, 6), (3, 2), (3, 5)]);
+    }
+
+    #[test]
+    fn scan_error_shows_line_and_column() {
+        let errors = Scanner::new("x\n  @").scan().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 2:3] Error: Unexpected character '@'."
+        );
+    }
+
+    // spaced out, since a contiguous run of garbage is one error
+    #[test]
+    fn every_scan_error_is_returned_in_order() {
+        let errors = Scanner::new("@ # $").scan().unwrap_err();
+        let columns: Vec<_> = errors.iter().map(|error| error.span.col).collect();
+        assert_eq!(columns, [1, 3, 5]);
+    }
+
+    fn string(src: &str) -> String {
+        match Scanner::new(src).scan().unwrap().remove(0).typ {
+            TokenType::Str(s) => s,
+            typ => panic!("expected a string, got {}", typ),
+        }
+    }
+
+    #[test]
+    fn string_escapes_are_decoded() {
+        assert_eq!(string(r#""a\nb""#), "a\nb");
+        assert_eq!(string(r#""\t""#), "\t");
+        assert_eq!(string(r#""\r""#), "\r");
+        assert_eq!(string(r#""\\""#), "\\");
+        assert_eq!(string(r#""say \"hi\"""#), "say \"hi\"");
+    }
+
+    #[test]
+    fn invalid_escape_is_reported_and_scanning_continues() {
+        let result = Scanner::new(r#""a\xb" 1"#).scan_all();
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].message, "Invalid escape sequence.");
+        assert_eq!(result.errors[0].span.col, 4);
+        assert_eq!(result.tokens[0].typ, TokenType::Str("axb".to_string()));
+        assert_eq!(result.tokens[1].typ, TokenType::Number(1.0));
+    }
+
+    #[test]
+    fn unicode_escapes_are_decoded() {
+        assert_eq!(string(r#""\u{1F600}""#), "\u{1F600}");
+        assert_eq!(string(r#""\u{e9}t\u{E9}""#), "été");
+    }
+
+    #[test]
+    fn malformed_unicode_escapes_are_errors() {
+        let cases = [
+            (r#""\u41""#, "Expect '{' after '\\u'."),
+            (r#""\u{41""#, "Expect '}' after unicode escape."),
+            (r#""\u{4g}""#, "Invalid hex digit in unicode escape."),
+            (r#""\u{}""#, "Empty unicode escape."),
+            (
+                r#""\u{d800}""#,
+                "Unicode escape is not a valid scalar value.",
+            ),
+            (
+                r#""\u{110000}""#,
+                "Unicode escape is not a valid scalar value.",
+            ),
+        ];
+        for (src, message) in cases {
+            let errors = Scanner::new(src).scan().unwrap_err();
+            assert_eq!(errors.len(), 1, "{}", src);
+            assert_eq!(errors[0].message, message, "{}", src);
+        }
+    }
+
+    #[test]
+    fn hex_and_binary_literals() {
+        assert_eq!(types("0xFF"), [TokenType::Number(255.0), TokenType::Eof]);
+        assert_eq!(types("0b1010"), [TokenType::Number(10.0), TokenType::Eof]);
+        assert_eq!(types("0.5"), [TokenType::Number(0.5), TokenType::Eof]);
+    }
+
+    #[test]
+    fn malformed_radix_literals_are_errors() {
+        let cases = [
+            ("0x", "Expect digits after number prefix."),
+            ("0b", "Expect digits after number prefix."),
+            ("0xFG", "Invalid digit 'G' in number literal."),
+            ("0b102", "Invalid digit '2' in number literal."),
+        ];
+        for (src, message) in cases {
+            let errors = Scanner::new(src).scan().unwrap_err();
+            assert_eq!(errors.len(), 1, "{}", src);
+            assert_eq!(errors[0].message, message, "{}", src);
+        }
+    }
+
+    #[test]
+    fn underscores_separate_digits() {
+        assert_eq!(
+            types("1_000.000_5"),
+            [TokenType::Number(1000.0005), TokenType::Eof]
+        );
+    }
+
+    #[test]
+    fn misplaced_underscores_are_errors() {
+        let errors = Scanner::new("1_").scan().unwrap_err();
+        assert_eq!(errors[0].message, "Trailing '_' in number literal.");
+        assert_eq!(errors[0].span.col, 2);
+        let errors = Scanner::new("1__0").scan().unwrap_err();
+        assert_eq!(errors[0].message, "Repeated '_' in number literal.");
+        assert_eq!(errors[0].span.col, 3);
+    }
+
+    #[test]
+    fn scientific_notation() {
+        assert_eq!(types("1e3"), [TokenType::Number(1000.0), TokenType::Eof]);
+        assert_eq!(types("2.5E-1"), [TokenType::Number(0.25), TokenType::Eof]);
+    }
+
+    #[test]
+    fn exponent_without_digits_is_an_error() {
+        let errors = Scanner::new("1e").scan().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Malformed exponent.");
+    }
+
+    #[test]
+    fn scanner_yields_tokens_lazily() {
+        let mut scanner = Scanner::new("a b c d");
+        let first: Vec<_> = scanner.by_ref().take(3).map(|token| token.lexeme).collect();
+        assert_eq!(first, ["a", "b", "c"]);
+        assert_eq!(scanner.next().unwrap().lexeme, "d");
+        assert_eq!(scanner.next().unwrap().typ, TokenType::Eof);
+        assert!(scanner.next().is_none());
+    }
+
+    #[test]
+    fn spans_slice_back_to_lexemes() {
+        let src = "var s = \"h\u{e9}llo\";\nprint s + 1.5;";
+        for token in Scanner::new(src).scan().unwrap() {
+            assert_eq!(&src[token.span.start..token.span.end], token.lexeme);
+        }
+        let tokens = Scanner::new(src).scan().unwrap();
+        assert_eq!(tokens[3].span.text(src), "\"h\u{e9}llo\"");
+    }
+
+    #[test]
+    fn garbage_run_is_one_error() {
+        let result = Scanner::new("###foo").scan_all();
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(
+            result.errors[0].message,
+            "Unexpected characters '###' in columns 1-3."
+        );
+        assert_eq!(result.tokens[0].typ, TokenType::Ident);
+        assert_eq!(result.tokens[0].lexeme, "foo");
+    }
+
+    #[test]
+    fn unexpected_character_names_the_whole_char() {
+        let errors = Scanner::new("@").scan().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 1:1] Error: Unexpected character '@'."
+        );
+        let src = "1 \u{20ac}";
+        let errors = Scanner::new(src).scan().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 1:3] Error: Unexpected character '\u{20ac}'."
+        );
+        assert_eq!(errors[0].span.text(src), "\u{20ac}");
+    }
+
+    #[test]
+    fn utf8_source_has_char_columns() {
+        let src = "print \"h\u{e9}llo\";\n\"\u{2192}\" + x;";
+        let tokens = Scanner::new(src).scan().unwrap();
+        let found: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.lexeme, token.span.line, token.span.col))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("print", 1, 1),
+                ("\"h\u{e9}llo\"", 1, 7),
+                (";", 1, 14),
+                ("\"\u{2192}\"", 2, 1),
+                ("+", 2, 5),
+                ("x", 2, 7),
+                (";", 2, 8),
+                ("", 2, 9)
+            ]
+        );
+        assert_eq!(tokens[1].typ, TokenType::Str("h\u{e9}llo".to_string()));
+    }
+
+    #[test]
+    fn columns_count_tabs_to_the_next_stop() {
+        let config = ScannerConfig {
+            tab_width: 4,
+            ..ScannerConfig::default()
+        };
+        let result = Scanner::with_config("\tab\t\"\u{e9}\\q\"", config).scan_all();
+        assert_eq!(result.tokens[0].span.col, 5);
+        assert_eq!(result.tokens[1].span.col, 9);
+        // the error is on the q of the invalid escape, after the two-byte char
+        assert_eq!(result.errors[0].span.col, 12);
+    }
+
+    #[test]
+    fn every_line_ending_style_counts_lines() {
+        for newline in ["\n", "\r\n", "\r"] {
+            let src = [
+                "var a = 1; // c",
+                "/* b",
+                "*/ var s = \"x",
+                "y\";",
+                "print s;",
+            ]
+            .join(newline);
+            let tokens = Scanner::new(&src).scan().unwrap();
+            let lines: Vec<_> = tokens
+                .iter()
+                .filter(|token| token.typ == TokenType::Var || token.typ == TokenType::Print)
+                .map(|token| token.span.line)
+                .collect();
+            assert_eq!(lines, [1, 3, 5], "{:?}", newline);
+            let s = tokens
+                .iter()
+                .find(|token| matches!(token.typ, TokenType::Str(_)));
+            assert_eq!(
+                s.unwrap().typ,
+                TokenType::Str(format!("x{}y", newline)),
+                "{:?}",
+                newline
+            );
+        }
+    }
+
+    #[test]
+    fn shebang_line_is_skipped() {
+        assert_eq!(
+            crate::run_to_string("#!/usr/bin/env lox\nprint 1;").unwrap(),
+            "1\n"
+        );
+        let errors = Scanner::new("#!/usr/bin/env lox\nprint 1;\n#")
+            .scan()
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span.line, 3);
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        let src = "\u{feff}print 1;";
+        let tokens = Scanner::new(src).scan().unwrap();
+        assert_eq!(tokens[0].typ, TokenType::Print);
+        assert_eq!((tokens[0].span.line, tokens[0].span.col), (1, 1));
+        assert_eq!(tokens[0].span.text(src), "print");
+    }
+
+    #[test]
+    fn tokens_display_their_type_lexeme_and_line() {
+        let tokens = Scanner::new("3 \"hi\"\nwhile x >=").scan().unwrap();
+        let shown: Vec<_> = tokens.iter().map(Token::to_string).collect();
+        assert_eq!(
+            shown,
+            [
+                "Number(3) \"3\" @ line 1",
+                "Str(\"hi\") \"\\\"hi\\\"\" @ line 1",
+                "while \"while\" @ line 2",
+                "identifier \"x\" @ line 2",
+                ">= \">=\" @ line 2",
+                "end of file \"\" @ line 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens_compare_directly() {
+        let span = |start, end| Span {
+            start,
+            end,
+            line: 1,
+            col: start + 1,
+        };
+        let expected = vec![
+            Token {
+                typ: TokenType::Ident,
+                lexeme: "a",
+                span: span(0, 1),
+            },
+            Token {
+                typ: TokenType::Equal,
+                lexeme: "=",
+                span: span(2, 3),
+            },
+            Token {
+                typ: TokenType::Number(0.5),
+                lexeme: "0.5",
+                span: span(4, 7),
+            },
+            Token {
+                typ: TokenType::Eof,
+                lexeme: "",
+                span: span(7, 7),
+            },
+        ];
+        assert_eq!(Scanner::new("a = 0.5").scan().unwrap(), expected);
+        // equality on numbers is f64 equality
+        assert_ne!(TokenType::Number(f64::NAN), TokenType::Number(f64::NAN));
+    }
+
+    #[test]
+    fn unterminated_string_spans_to_the_end() {
+        let src = "print 1;\nvar s = \"abc\ndef";
+        let errors = Scanner::new(src).scan().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let span = errors[0].span;
+        assert_eq!((span.start, span.end), (17, src.len()));
+        assert_eq!((span.line, span.col), (2, 9));
+        assert_eq!(span.text(src), "\"abc\ndef");
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 2:9] Error: Unterminated string (reached end of input on line 3)."
+        );
+    }
+
+    #[test]
+    fn file_that_is_only_a_comment() {
+        for src in ["// comment", "// comment\n"] {
+            let tokens = Scanner::new(src).scan().unwrap();
+            assert_eq!(tokens.len(), 1, "{:?}", src);
+            assert_eq!(tokens[0].typ, TokenType::Eof);
+            assert_eq!(tokens[0].span.start, src.len());
+        }
+        let tokens = Scanner::new("// comment\n").scan().unwrap();
+        assert_eq!((tokens[0].span.line, tokens[0].span.col), (2, 1));
+    }
+
+    #[test]
+    fn unterminated_string_is_reported_where_it_starts() {
+        let errors = Scanner::new("print 1;\nprint \"abc\n\n")
+            .scan()
+            .unwrap_err();
+        assert_eq!(errors[0].span.line, 2);
+        assert_eq!(
+            errors[0].message,
+            "Unterminated string (reached end of input on line 4)."
+        );
+        let errors = Scanner::new("\"abc").scan().unwrap_err();
+        assert_eq!(errors[0].message, "Unterminated string.");
+    }
+
+    #[test]
+    fn comment_nesting_past_the_limit_is_one_error() {
+        let result = Scanner::new("/* /* /* /* */ */ */ */ x")
+            .with_max_comment_depth(2)
+            .scan_all();
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].message, "Too much nesting.");
+        assert_eq!(result.errors[0].span.col, 7);
+        // the comment is still skipped to its real end
+        assert_eq!(result.tokens[0].lexeme, "x");
+        let src = format!("{}{}", "/*".repeat(100_000), "*/".repeat(100_000));
+        assert_eq!(Scanner::new(&src).scan().unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn percent_is_a_token() {
+        assert_eq!(
+            types("7 % 3"),
+            [
+                TokenType::Number(7.0),
+                TokenType::Percent,
+                TokenType::Number(3.0),
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_all_keeps_tokens_alongside_errors() {
+        let ScanResult { tokens, errors } = Scanner::new("\"unterminated").scan_all();
+        assert_eq!(
+            tokens.iter().map(|token| &token.typ).collect::<Vec<_>>(),
+            [&TokenType::Eof]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Unterminated string.");
+    }
+
+    #[test]
+    fn scan_all_orders_errors_by_position() {
+        let ScanResult { tokens, errors } = Scanner::new("@ 1 # 2 /* 3").scan_all();
+        assert_eq!(tokens.len(), 3);
+        let starts: Vec<_> = errors.iter().map(|error| error.span.start).collect();
+        assert_eq!(starts, [0, 4, 8]);
+    }
+
+    fn types_with(config: ScannerConfig, src: &str) -> Vec<TokenType> {
+        Scanner::with_config(src, config)
+            .scan()
+            .unwrap()
+            .into_iter()
+            .map(|token| token.typ)
+            .collect()
+    }
+
+    #[test]
+    fn strict_and_extended_configs_scan_differently() {
+        let src = "break /* c */ 1e3";
+        assert_eq!(
+            types_with(ScannerConfig::default(), src),
+            [TokenType::Break, TokenType::Number(1000.0), TokenType::Eof]
+        );
+        assert_eq!(
+            types_with(ScannerConfig::strict(), src),
+            [
+                TokenType::Ident,
+                TokenType::Slash,
+                TokenType::Star,
+                TokenType::Ident,
+                TokenType::Star,
+                TokenType::Slash,
+                TokenType::Number(1.0),
+                TokenType::Ident,
+                TokenType::Eof
+            ]
+        );
+        // Scanner::new keeps the extended defaults
+        assert_eq!(types(src), types_with(ScannerConfig::default(), src));
+    }
+
+    #[test]
+    fn leading_dot_numbers_are_opt_in() {
+        assert_eq!(
+            types(".5"),
+            [TokenType::Dot, TokenType::Number(5.0), TokenType::Eof]
+        );
+        let config = ScannerConfig {
+            leading_dot_numbers: true,
+            ..ScannerConfig::default()
+        };
+        assert_eq!(
+            types_with(config.clone(), ".5"),
+            [TokenType::Number(0.5), TokenType::Eof]
+        );
+        // property access still scans as a dot
+        assert_eq!(
+            types_with(config, "a.b"),
+            [
+                TokenType::Ident,
+                TokenType::Dot,
+                TokenType::Ident,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    // rescanning old after it's edited into new at offset gives the same tokens as a fresh scan
+    fn assert_rescan_matches(config: ScannerConfig, old: &str, offset: usize, new: &str) {
+        let prev = Scanner::with_config(old, config.clone()).scan_all().tokens;
+        let incremental = Scanner::rescan_from(&prev, offset, new, config.clone()).tokens;
+        let full = Scanner::with_config(new, config).scan_all().tokens;
+        assert_eq!(incremental, full, "{:?} -> {:?}", old, new);
+    }
+
+    #[test]
+    fn incremental_rescan_matches_a_full_one() {
+        let edits = [
+            // the number before the edit peeked two bytes ahead to rule out a fraction
+            ("x = 1.", 6, "x = 1.5"),
+            ("var a = 1;", 5, "var ab = 1;"),
+            ("print 1;\nprint 2;", 16, "print 1;\nprint 23;"),
+            ("print 1;\nprint 2;", 8, "print 1;\nvar y;\nprint 2;"),
+            ("a = \"x${b}y\";", 8, "a = \"x${bc}y\";"),
+            ("a // b\nc", 2, "a /* b\nc"),
+            ("a = 1; ", 7, "a = 1; \"open"),
+        ];
+        for (old, offset, new) in edits {
+            assert_rescan_matches(ScannerConfig::default(), old, offset, new);
+        }
+        assert_rescan_matches(ScannerConfig::strict(), "x = 1; break", 12, "x = 1; break;");
+        assert_rescan_matches(ScannerConfig::strict(), "x = 1e", 6, "x = 1e3");
+    }
+
+    #[test]
+    fn single_quoted_strings_hold_double_quotes() {
+        assert_eq!(string(r#"'he said "hi"'"#), "he said \"hi\"");
+        assert_eq!(string(r#""it's""#), "it's");
+        assert_eq!(string(r"'it\'s\n'"), "it's\n");
+    }
+
+    #[test]
+    fn string_must_close_with_its_opening_quote() {
+        let errors = Scanner::new("'abc\"; print 1;").scan().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Unterminated string.");
+        assert_eq!(errors[0].span.start, 0);
+        // the book's dialect has no single-quoted strings
+        let errors = Scanner::with_config("'a'", ScannerConfig::strict())
+            .scan()
+            .unwrap_err();
+        assert_eq!(errors[0].message, r"Unexpected character '\''.");
+    }
+
+    #[test]
+    fn reconstructed_source_scans_to_the_same_tokens() {
+        let src = "class A < B {\n  f(x) { return -x ** 2 >= 1.5 and !nil; }\n}\n\
+                   var s = \"tab\\there \\\"quoted\\\" \\\\ ${1 + {\"k\": 2}[\"k\"]} end\";\n\
+                   print a.b(c)[0] - -1 + s; i++; j -= 3 % 2; // done";
+        let tokens = Scanner::new(src).scan().unwrap();
+        let rebuilt = tokens_to_source(&tokens);
+        let retyped: Vec<_> = Scanner::new(&rebuilt)
+            .scan()
+            .unwrap()
+            .into_iter()
+            .map(|token| token.typ)
+            .collect();
+        assert_eq!(retyped, types(src), "{}", rebuilt);
+    }
+
+    #[test]
+    fn count_tokens_matches_a_full_scan() {
+        let programs = [
+            "",
+            "print 1;",
+            "var s = \"a${b + 1}c\"; // comment\n/* block */ s += 'x';",
+            "class A { f() { return this.x ** 2; } }",
+        ];
+        for src in programs {
+            assert_eq!(
+                Scanner::count_tokens(src),
+                Scanner::new(src).scan().unwrap().len(),
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn number_literal_too_big_for_f64_is_an_error() {
+        let errors = Scanner::new("print 1e400;").scan().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Number literal out of range.");
+        assert_eq!((errors[0].span.start, errors[0].span.end), (6, 11));
+        let long = "9".repeat(400);
+        let errors = Scanner::new(&long).scan().unwrap_err();
+        assert_eq!(errors[0].message, "Number literal out of range.");
+        assert_eq!(errors[0].span.end, 400);
+        // a long run that still fits is rounded rather than rejected
+        let fits = format!("1{}", "0".repeat(300));
+        assert_eq!(types(&fits), [TokenType::Number(1e300), TokenType::Eof]);
+        assert_eq!(
+            types(&"0".repeat(1000)),
+            [TokenType::Number(0.0), TokenType::Eof]
+        );
+    }
+
+    #[test]
+    fn keyword_case_is_only_ignored_when_enabled() {
+        let src = "IF If if Iffy";
+        assert_eq!(
+            types(src),
+            [
+                TokenType::Ident,
+                TokenType::Ident,
+                TokenType::If,
+                TokenType::Ident,
+                TokenType::Eof
+            ]
+        );
+        let config = ScannerConfig {
+            case_insensitive_keywords: true,
+            ..ScannerConfig::default()
+        };
+        assert_eq!(
+            types_with(config, src),
+            [
+                TokenType::If,
+                TokenType::If,
+                TokenType::If,
+                TokenType::Ident,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn star_star_is_one_token() {
+        assert_eq!(
+            types("2 ** 3 * 4"),
+            [
+                TokenType::Number(2.0),
+                TokenType::StarStar,
+                TokenType::Number(3.0),
+                TokenType::Star,
+                TokenType::Number(4.0),
+                TokenType::Eof
+            ]
+        );
+    }
+}
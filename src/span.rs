@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+// a region of source: byte offsets for slicing, plus the line and column it starts at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    // the smallest span covering both, positioned wherever the earlier one starts
+    pub fn merge(self, other: Span) -> Span {
+        let first = if other.start < self.start {
+            other
+        } else {
+            self
+        };
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            line: first.line,
+            col: first.col,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    // the covered source text; src must be the source the span came from
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.start..self.end]
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize, col: usize) -> Span {
+        Span {
+            start,
+            end,
+            line: 1,
+            col,
+        }
+    }
+
+    #[test]
+    fn merge_covers_both_from_wherever_the_earlier_starts() {
+        let (left, right) = (span(6, 7, 7), span(10, 12, 11));
+        assert_eq!(left.merge(right), span(6, 12, 7));
+        assert_eq!(right.merge(left), span(6, 12, 7));
+        // one inside the other is just the outer one
+        assert_eq!(span(0, 20, 1).merge(left), span(0, 20, 1));
+    }
+
+    #[test]
+    fn text_is_the_source_covered() {
+        let src = "print 1 + 22;";
+        let merged = span(6, 7, 7).merge(span(10, 12, 11));
+        assert_eq!(merged.text(src), "1 + 22");
+        assert_eq!(merged.len(), 6);
+        assert_eq!(merged.to_string(), "1:7");
+    }
+}
@@ -0,0 +1,367 @@
+use std::rc::Rc;
+
+use crate::{
+    BinaryOp, Expr, ExprId, FunctionDecl, Literal, LogicalOp, Span, Stmt, UnaryOp, UpdateOp,
+};
+
+// a pass over expressions, with one method per variant taking its fields
+pub trait ExprVisitor<T> {
+    fn visit_literal_expr(&mut self, value: &Literal, span: Span) -> T;
+    fn visit_variable_expr(&mut self, id: ExprId, name: &str, span: Span) -> T;
+    fn visit_assign_expr(&mut self, id: ExprId, name: &str, span: Span, value: &Expr) -> T;
+    fn visit_unary_expr(&mut self, op: UnaryOp, span: Span, right: &Expr) -> T;
+    fn visit_binary_expr(&mut self, left: &Expr, op: BinaryOp, span: Span, right: &Expr) -> T;
+    fn visit_logical_expr(&mut self, left: &Expr, op: LogicalOp, span: Span, right: &Expr) -> T;
+    fn visit_comma_expr(&mut self, left: &Expr, span: Span, right: &Expr) -> T;
+    fn visit_ternary_expr(
+        &mut self,
+        condition: &Expr,
+        span: Span,
+        then_branch: &Expr,
+        else_branch: &Expr,
+    ) -> T;
+    fn visit_lambda_expr(&mut self, decl: &Rc<FunctionDecl>) -> T;
+    fn visit_call_expr(&mut self, callee: &Expr, span: Span, args: &[Expr]) -> T;
+    fn visit_get_expr(&mut self, object: &Expr, name: &str, span: Span) -> T;
+    fn visit_set_expr(&mut self, object: &Expr, name: &str, span: Span, value: &Expr) -> T;
+    fn visit_list_expr(&mut self, span: Span, elements: &[Expr]) -> T;
+    fn visit_map_expr(&mut self, span: Span, entries: &[(Expr, Expr)]) -> T;
+    fn visit_index_expr(&mut self, object: &Expr, span: Span, index: &Expr) -> T;
+    fn visit_set_index_expr(&mut self, object: &Expr, span: Span, index: &Expr, value: &Expr) -> T;
+    fn visit_update_expr(&mut self, op: UpdateOp, prefix: bool, span: Span, target: &Expr) -> T;
+    fn visit_compound_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &str,
+        span: Span,
+        op: BinaryOp,
+        value: &Expr,
+    ) -> T;
+    fn visit_this_expr(&mut self, id: ExprId, span: Span) -> T;
+    fn visit_super_expr(&mut self, id: ExprId, span: Span, method: &str) -> T;
+    fn visit_grouping_expr(&mut self, span: Span, expr: &Expr) -> T;
+}
+
+// a pass over statements, with one method per variant taking its fields
+pub trait StmtVisitor<T> {
+    fn visit_expression_stmt(&mut self, expr: &Expr) -> T;
+    fn visit_print_stmt(&mut self, span: Span, expr: &Expr) -> T;
+    fn visit_var_stmt(&mut self, name: &str, span: Span, initializer: Option<&Expr>) -> T;
+    fn visit_block_stmt(&mut self, span: Span, stmts: &[Stmt]) -> T;
+    fn visit_if_stmt(
+        &mut self,
+        span: Span,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> T;
+    fn visit_while_stmt(
+        &mut self,
+        span: Span,
+        condition: &Expr,
+        body: &Stmt,
+        increment: Option<&Expr>,
+    ) -> T;
+    fn visit_break_stmt(&mut self, span: Span) -> T;
+    fn visit_continue_stmt(&mut self, span: Span) -> T;
+    fn visit_function_stmt(&mut self, decl: &Rc<FunctionDecl>) -> T;
+    fn visit_return_stmt(&mut self, span: Span, value: Option<&Expr>) -> T;
+    fn visit_class_stmt(
+        &mut self,
+        name: &str,
+        span: Span,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> T;
+}
+
+impl Expr {
+    pub fn accept<T>(&self, visitor: &mut impl ExprVisitor<T>) -> T {
+        match self {
+            Expr::Literal { value, span } => visitor.visit_literal_expr(value, *span),
+            Expr::Variable { id, name, span } => visitor.visit_variable_expr(*id, name, *span),
+            Expr::Assign {
+                id,
+                name,
+                span,
+                value,
+            } => visitor.visit_assign_expr(*id, name, *span, value),
+            Expr::Unary { op, span, right } => visitor.visit_unary_expr(*op, *span, right),
+            Expr::Binary {
+                left,
+                op,
+                span,
+                right,
+            } => visitor.visit_binary_expr(left, *op, *span, right),
+            Expr::Logical {
+                left,
+                op,
+                span,
+                right,
+            } => visitor.visit_logical_expr(left, *op, *span, right),
+            Expr::Comma { left, span, right } => visitor.visit_comma_expr(left, *span, right),
+            Expr::Ternary {
+                condition,
+                span,
+                then_branch,
+                else_branch,
+            } => visitor.visit_ternary_expr(condition, *span, then_branch, else_branch),
+            Expr::Lambda(decl) => visitor.visit_lambda_expr(decl),
+            Expr::Call { callee, span, args } => visitor.visit_call_expr(callee, *span, args),
+            Expr::Get { object, name, span } => visitor.visit_get_expr(object, name, *span),
+            Expr::Set {
+                object,
+                name,
+                span,
+                value,
+            } => visitor.visit_set_expr(object, name, *span, value),
+            Expr::List { span, elements } => visitor.visit_list_expr(*span, elements),
+            Expr::Map { span, entries } => visitor.visit_map_expr(*span, entries),
+            Expr::Index {
+                object,
+                span,
+                index,
+            } => visitor.visit_index_expr(object, *span, index),
+            Expr::SetIndex {
+                object,
+                span,
+                index,
+                value,
+            } => visitor.visit_set_index_expr(object, *span, index, value),
+            Expr::Update {
+                op,
+                prefix,
+                span,
+                target,
+            } => visitor.visit_update_expr(*op, *prefix, *span, target),
+            Expr::CompoundSet {
+                object,
+                name,
+                span,
+                op,
+                value,
+            } => visitor.visit_compound_set_expr(object, name, *span, *op, value),
+            Expr::This { id, span } => visitor.visit_this_expr(*id, *span),
+            Expr::Super { id, span, method } => visitor.visit_super_expr(*id, *span, method),
+            Expr::Grouping { span, expr } => visitor.visit_grouping_expr(*span, expr),
+        }
+    }
+}
+
+impl Stmt {
+    pub fn accept<T>(&self, visitor: &mut impl StmtVisitor<T>) -> T {
+        match self {
+            Stmt::Expression(expr) => visitor.visit_expression_stmt(expr),
+            Stmt::Print { span, expr } => visitor.visit_print_stmt(*span, expr),
+            Stmt::Var {
+                name,
+                span,
+                initializer,
+            } => visitor.visit_var_stmt(name, *span, initializer.as_ref()),
+            Stmt::Block { span, stmts } => visitor.visit_block_stmt(*span, stmts),
+            Stmt::If {
+                span,
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_if_stmt(*span, condition, then_branch, else_branch.as_deref()),
+            Stmt::While {
+                span,
+                condition,
+                body,
+                increment,
+            } => visitor.visit_while_stmt(*span, condition, body, increment.as_ref()),
+            Stmt::Break { span } => visitor.visit_break_stmt(*span),
+            Stmt::Continue { span } => visitor.visit_continue_stmt(*span),
+            Stmt::Function(decl) => visitor.visit_function_stmt(decl),
+            Stmt::Return { span, value } => visitor.visit_return_stmt(*span, value.as_ref()),
+            Stmt::Class {
+                name,
+                span,
+                superclass,
+                methods,
+            } => visitor.visit_class_stmt(name, *span, superclass.as_ref(), methods),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, Scanner};
+
+    // counts the literals in a program, including those inside function bodies
+    struct LiteralCounter;
+
+    impl LiteralCounter {
+        fn exprs<'a>(&mut self, exprs: impl IntoIterator<Item = &'a Expr>) -> usize {
+            exprs.into_iter().map(|expr| expr.accept(self)).sum()
+        }
+
+        fn stmts(&mut self, stmts: &[Stmt]) -> usize {
+            stmts.iter().map(|stmt| stmt.accept(self)).sum()
+        }
+    }
+
+    impl ExprVisitor<usize> for LiteralCounter {
+        fn visit_literal_expr(&mut self, _: &Literal, _: Span) -> usize {
+            1
+        }
+        fn visit_variable_expr(&mut self, _: ExprId, _: &str, _: Span) -> usize {
+            0
+        }
+        fn visit_assign_expr(&mut self, _: ExprId, _: &str, _: Span, value: &Expr) -> usize {
+            value.accept(self)
+        }
+        fn visit_unary_expr(&mut self, _: UnaryOp, _: Span, right: &Expr) -> usize {
+            right.accept(self)
+        }
+        fn visit_binary_expr(&mut self, left: &Expr, _: BinaryOp, _: Span, right: &Expr) -> usize {
+            self.exprs([left, right])
+        }
+        fn visit_logical_expr(
+            &mut self,
+            left: &Expr,
+            _: LogicalOp,
+            _: Span,
+            right: &Expr,
+        ) -> usize {
+            self.exprs([left, right])
+        }
+        fn visit_comma_expr(&mut self, left: &Expr, _: Span, right: &Expr) -> usize {
+            self.exprs([left, right])
+        }
+        fn visit_ternary_expr(
+            &mut self,
+            condition: &Expr,
+            _: Span,
+            then_branch: &Expr,
+            else_branch: &Expr,
+        ) -> usize {
+            self.exprs([condition, then_branch, else_branch])
+        }
+        fn visit_lambda_expr(&mut self, decl: &Rc<FunctionDecl>) -> usize {
+            self.stmts(&decl.body)
+        }
+        fn visit_call_expr(&mut self, callee: &Expr, _: Span, args: &[Expr]) -> usize {
+            callee.accept(self) + self.exprs(args)
+        }
+        fn visit_get_expr(&mut self, object: &Expr, _: &str, _: Span) -> usize {
+            object.accept(self)
+        }
+        fn visit_set_expr(&mut self, object: &Expr, _: &str, _: Span, value: &Expr) -> usize {
+            self.exprs([object, value])
+        }
+        fn visit_list_expr(&mut self, _: Span, elements: &[Expr]) -> usize {
+            self.exprs(elements)
+        }
+        fn visit_map_expr(&mut self, _: Span, entries: &[(Expr, Expr)]) -> usize {
+            self.exprs(entries.iter().flat_map(|(key, value)| [key, value]))
+        }
+        fn visit_index_expr(&mut self, object: &Expr, _: Span, index: &Expr) -> usize {
+            self.exprs([object, index])
+        }
+        fn visit_set_index_expr(
+            &mut self,
+            object: &Expr,
+            _: Span,
+            index: &Expr,
+            value: &Expr,
+        ) -> usize {
+            self.exprs([object, index, value])
+        }
+        fn visit_update_expr(&mut self, _: UpdateOp, _: bool, _: Span, target: &Expr) -> usize {
+            target.accept(self)
+        }
+        fn visit_compound_set_expr(
+            &mut self,
+            object: &Expr,
+            _: &str,
+            _: Span,
+            _: BinaryOp,
+            value: &Expr,
+        ) -> usize {
+            self.exprs([object, value])
+        }
+        fn visit_this_expr(&mut self, _: ExprId, _: Span) -> usize {
+            0
+        }
+        fn visit_super_expr(&mut self, _: ExprId, _: Span, _: &str) -> usize {
+            0
+        }
+        fn visit_grouping_expr(&mut self, _: Span, expr: &Expr) -> usize {
+            expr.accept(self)
+        }
+    }
+
+    impl StmtVisitor<usize> for LiteralCounter {
+        fn visit_expression_stmt(&mut self, expr: &Expr) -> usize {
+            expr.accept(self)
+        }
+        fn visit_print_stmt(&mut self, _: Span, expr: &Expr) -> usize {
+            expr.accept(self)
+        }
+        fn visit_var_stmt(&mut self, _: &str, _: Span, initializer: Option<&Expr>) -> usize {
+            self.exprs(initializer)
+        }
+        fn visit_block_stmt(&mut self, _: Span, stmts: &[Stmt]) -> usize {
+            self.stmts(stmts)
+        }
+        fn visit_if_stmt(
+            &mut self,
+            _: Span,
+            condition: &Expr,
+            then_branch: &Stmt,
+            else_branch: Option<&Stmt>,
+        ) -> usize {
+            condition.accept(self)
+                + then_branch.accept(self)
+                + else_branch.map_or(0, |stmt| stmt.accept(self))
+        }
+        fn visit_while_stmt(
+            &mut self,
+            _: Span,
+            condition: &Expr,
+            body: &Stmt,
+            increment: Option<&Expr>,
+        ) -> usize {
+            condition.accept(self) + body.accept(self) + self.exprs(increment)
+        }
+        fn visit_break_stmt(&mut self, _: Span) -> usize {
+            0
+        }
+        fn visit_continue_stmt(&mut self, _: Span) -> usize {
+            0
+        }
+        fn visit_function_stmt(&mut self, decl: &Rc<FunctionDecl>) -> usize {
+            self.stmts(&decl.body)
+        }
+        fn visit_return_stmt(&mut self, _: Span, value: Option<&Expr>) -> usize {
+            self.exprs(value)
+        }
+        fn visit_class_stmt(
+            &mut self,
+            _: &str,
+            _: Span,
+            superclass: Option<&Expr>,
+            methods: &[Rc<FunctionDecl>],
+        ) -> usize {
+            self.exprs(superclass)
+                + methods
+                    .iter()
+                    .map(|decl| self.stmts(&decl.body))
+                    .sum::<usize>()
+        }
+    }
+
+    #[test]
+    fn visitor_counts_literal_nodes() {
+        let src = "var a = 1 + 2 * (3 - a);\n\
+                   fun f(x) { if (x) return [4, \"five\"]; else return nil; }\n\
+                   print f(true) ? {6: 7} : a;";
+        let stmts = Parser::new(Scanner::new(src).scan().unwrap())
+            .parse_program()
+            .unwrap();
+        assert_eq!(LiteralCounter.stmts(&stmts), 9);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{Chunk, OpCode, RuntimeError, Span, Value};
+
+// executes a chunk on a value stack
+pub struct VM {
+    chunk: Chunk,
+    ip: usize,
+    stack: Vec<Value>,
+    // the source each value on the stack was computed from, so errors can blame an operand
+    spans: Vec<Span>,
+}
+
+impl VM {
+    pub fn new(chunk: Chunk) -> VM {
+        VM {
+            chunk,
+            ip: 0,
+            stack: vec![],
+            spans: vec![],
+        }
+    }
+
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    // runs until a Return, producing the value it pops
+    pub fn run(&mut self) -> Result<Value, RuntimeError> {
+        loop {
+            let byte = self.read_byte();
+            let op = OpCode::try_from(byte).map_err(|byte| {
+                error(
+                    self.instruction_span(),
+                    &format!("Unknown opcode {}.", byte),
+                )
+            })?;
+            match op {
+                OpCode::Constant => {
+                    let index = self.read_byte();
+                    let value = self.chunk.constants[index as usize].clone();
+                    self.push(value, self.chunk.span_at(self.ip - 2));
+                }
+                OpCode::Add => {
+                    let ((a, a_span), (b, b_span)) = self.pop_pair();
+                    let value = match (a, b) {
+                        (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                        (Value::Str(a), Value::Str(b)) => Value::Str(a + &b),
+                        (a, _) => {
+                            let span = match a {
+                                Value::Number(_) | Value::Str(_) => b_span,
+                                _ => a_span,
+                            };
+                            return Err(error(
+                                span,
+                                "Operands must be two numbers or two strings.",
+                            ));
+                        }
+                    };
+                    self.push(value, a_span.merge(b_span));
+                }
+                OpCode::Subtract => self.arithmetic(|a, b| a - b)?,
+                OpCode::Multiply => self.arithmetic(|a, b| a * b)?,
+                OpCode::Divide => self.arithmetic(|a, b| a / b)?,
+                OpCode::Modulo => self.arithmetic(|a, b| a % b)?,
+                OpCode::Negate => match self.pop() {
+                    (Value::Number(n), span) => {
+                        self.push(Value::Number(-n), self.instruction_span().merge(span))
+                    }
+                    (_, span) => return Err(error(span, "Operand must be a number.")),
+                },
+                OpCode::Return => return Ok(self.pop().0),
+            }
+        }
+    }
+
+    fn arithmetic(&mut self, op: impl Fn(f64, f64) -> f64) -> Result<(), RuntimeError> {
+        match self.pop_pair() {
+            ((Value::Number(a), a_span), (Value::Number(b), b_span)) => {
+                // the result stands for both operands, since the instruction is just the operator
+                self.push(Value::Number(op(a, b)), a_span.merge(b_span));
+                Ok(())
+            }
+            ((Value::Number(_), _), (_, span)) | ((_, span), _) => {
+                Err(error(span, "Operands must be numbers."))
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let byte = self.chunk.code[self.ip];
+        self.ip += 1;
+        byte
+    }
+
+    fn push(&mut self, value: Value, span: Span) {
+        self.stack.push(value);
+        self.spans.push(span);
+    }
+
+    // the compiler never emits an instruction without its operands on the stack
+    fn pop(&mut self) -> (Value, Span) {
+        let value = self.stack.pop().expect("value stack underflow");
+        (value, self.spans.pop().expect("span stack underflow"))
+    }
+
+    fn pop_pair(&mut self) -> ((Value, Span), (Value, Span)) {
+        let b = self.pop();
+        let a = self.pop();
+        (a, b)
+    }
+
+    // the span of the instruction being executed
+    fn instruction_span(&self) -> Span {
+        self.chunk.span_at(self.ip - 1)
+    }
+}
+
+// span is the whole of the operand or instruction at fault, for a caret diagnostic
+fn error(span: Span, message: &str) -> RuntimeError {
+    RuntimeError {
+        span,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile_to_chunk, Parser, Scanner};
+
+    fn vm(src: &str) -> VM {
+        let expr = Parser::new(Scanner::new(src).scan().unwrap())
+            .parse()
+            .unwrap();
+        VM::new(compile_to_chunk(&expr).unwrap())
+    }
+
+    #[test]
+    fn runs_arithmetic_and_empties_the_stack() {
+        let mut vm = vm("(-1 + 2) * 3");
+        assert_eq!(vm.run().unwrap(), Value::Number(3.0));
+        assert!(vm.stack().is_empty());
+    }
+
+    #[test]
+    fn type_error_is_reported_on_its_line() {
+        let error = vm("1 +\n-\"a\"").run().unwrap_err();
+        assert_eq!(error.message, "Operand must be a number.");
+        assert_eq!(error.span.line, 2);
+    }
+
+    #[test]
+    fn runs_remainder() {
+        assert_eq!(vm("7 % 3").run().unwrap(), Value::Number(1.0));
+    }
+
+    #[test]
+    fn type_error_blames_the_operand_at_fault() {
+        let spans = |src| {
+            let span = vm(src).run().unwrap_err().span;
+            (span.start, span.end)
+        };
+        // the left operand if it has the wrong type, else the right one
+        assert_eq!(spans("nil * (2 + 3)"), (0, 3));
+        assert_eq!(spans("1 - \"ab\""), (4, 8));
+        assert_eq!(spans("\"a\" + 1"), (6, 7));
+        // a computed operand is blamed as a whole, though without its parentheses, which
+        // compile to nothing
+        assert_eq!(spans("-1 + (\"a\" + \"b\") - 2"), (6, 15));
+        assert_eq!(spans("1 + -nil"), (5, 8));
+    }
+}
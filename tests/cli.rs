@@ -0,0 +1,197 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+// runs the binary with args, feeding it stdin
+fn lox(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// a script file with the given source, unique to the test that writes it
+fn script(name: &str, src: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lox-cli-{}-{}.lox", std::process::id(), name));
+    std::fs::write(&path, src).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn runtime_error_exits_70() {
+    let path = script("runtime_error", "print 1;\nprint -\"a\";");
+    let output = lox(&[path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).contains("Operand must be a number."));
+}
+
+#[test]
+fn compile_errors_exit_65() {
+    for (name, src) in [("scan_error", "print @;"), ("parse_error", "print ;")] {
+        let path = script(name, src);
+        let output = lox(&[path.to_str().unwrap()], "");
+        assert_eq!(output.status.code(), Some(65), "{}", src);
+    }
+}
+
+#[test]
+fn tokens_flag_lists_tokens_instead_of_running() {
+    let path = script("tokens", "print 1;");
+    let output = lox(&["--tokens", path.to_str().unwrap()], "");
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "1:1\tprint\t\"print\"\n1:7\tNumber(1)\t\"1\"\n1:8\t;\t\";\"\n1:9\tend of file\t\"\"\n"
+    );
+}
+
+#[test]
+fn program_is_read_from_stdin() {
+    for args in [&["-"][..], &[]] {
+        let output = lox(args, "print 1 + 2;\nprint \"ok\";\n");
+        assert!(output.status.success(), "{:?}", args);
+        // no prompt, since piped input isn't a REPL session
+        assert_eq!(stdout(&output), "3\nok\n", "{:?}", args);
+    }
+}
+
+#[test]
+fn check_flag_reports_without_running() {
+    let path = script("check_error", "print 1;\nprint 2\n");
+    let output = lox(&["--check", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("Expect ';' after value."));
+    let path = script("check_ok", "print 1;");
+    let output = lox(&["--check", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn caret_lines_up_with_the_column_for_any_tab_width() {
+    let path = script("tab_width", "\tprint @;");
+    let output = lox(&[path.to_str().unwrap()], "");
+    assert_eq!(
+        stderr(&output),
+        "[line 1:8] Error: Unexpected character '@'.\n  |\n1 |  print @;\n  |        ^\n"
+    );
+    let output = lox(&["--tab-width=8", path.to_str().unwrap()], "");
+    let expected = "[line 1:15] Error: Unexpected character '@'.\n  |\n\
+                    1 |         print @;\n  |               ^\n";
+    assert_eq!(stderr(&output), expected);
+    let output = lox(&["--tab-width=0", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn vm_errors_underline_the_operand() {
+    let output = lox(&["--vm"], "1 + 2 * 3");
+    assert_eq!(stdout(&output), "7\n");
+    let output = lox(&["--vm"], "2 * (1 + nil)");
+    assert_eq!(output.status.code(), Some(70));
+    let expected = "[line 1] Error: Operands must be two numbers or two strings.\n  |\n\
+                    1 | 2 * (1 + nil)\n  |          ^^^\n";
+    assert_eq!(stderr(&output), expected);
+}
+
+#[test]
+fn time_flag_prints_one_phase_per_line() {
+    let path = script("time", "print 1;");
+    let output = lox(&["--time", path.to_str().unwrap()], "");
+    assert_eq!(stdout(&output), "1\n");
+    let stderr = stderr(&output);
+    let phases: Vec<_> = stderr
+        .lines()
+        .map(|line| {
+            let (phase, ms) = line.split_once('=').unwrap();
+            assert!(ms.parse::<f64>().is_ok_and(|ms| ms >= 0.0), "{}", line);
+            phase
+        })
+        .collect();
+    assert_eq!(phases, ["scan", "parse", "resolve", "interpret"]);
+}
+
+#[test]
+fn chained_comparison_is_warned_about_before_folding() {
+    let path = script("chained_opt", "print 1 < 2 < 3;");
+    let output = lox(&["--opt", path.to_str().unwrap()], "");
+    assert!(stderr(&output).contains("Chained comparison does not mean what you think"));
+}
+
+#[test]
+fn script_runtime_errors_underline_the_operator() {
+    let path = script("runtime_caret", "print 1;\nprint 2 * \"a\";");
+    let output = lox(&[path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(70));
+    let expected = "[line 2] Error: Operands must be numbers.\n  |\n\
+                    2 | print 2 * \"a\";\n  |         ^\n";
+    assert_eq!(stderr(&output), expected);
+}
+
+// round-trips what --emit-json prints, so the documented token shape is checked end to end
+#[cfg(feature = "serde")]
+#[test]
+fn emit_json_prints_tokens_in_the_serde_shape() {
+    let output = lox(&["--emit-json", "-"], "1 + \"a\"");
+    assert!(output.status.success());
+    let tokens: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        tokens[0],
+        serde_json::json!({
+            "type": "Number",
+            "value": 1.0,
+            "lexeme": "1",
+            "span": {"start": 0, "end": 1, "line": 1, "col": 1},
+        })
+    );
+    assert_eq!(
+        tokens[1],
+        serde_json::json!({
+            "type": "Plus",
+            "lexeme": "+",
+            "span": {"start": 2, "end": 3, "line": 1, "col": 3},
+        })
+    );
+    assert_eq!(tokens[2]["value"], "a");
+    assert_eq!(tokens[3]["type"], "Eof");
+
+    let output = lox(&["--emit-json", "-"], "print @;");
+    assert_eq!(output.status.code(), Some(65));
+    let error: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(error["message"], "Unexpected character '@'.");
+    assert_eq!(error["span"]["col"], 7);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn ast_json_prints_the_serde_tree() {
+    let output = lox(&["--ast-json", "-"], "print -1;");
+    assert!(output.status.success());
+    let stmts: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(stmts[0]["type"], "Print");
+    assert_eq!(stmts[0]["expr"]["type"], "Unary");
+    assert_eq!(stmts[0]["expr"]["op"], "-");
+    assert_eq!(stmts[0]["expr"]["right"]["value"], 1.0);
+}
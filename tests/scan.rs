@@ -0,0 +1,18 @@
+use lox::{Scanner, TokenType};
+
+#[test]
+fn scans_through_the_public_api() {
+    let tokens = Scanner::new("print 1 + x;").scan().unwrap();
+    let types: Vec<_> = tokens.into_iter().map(|token| token.typ).collect();
+    assert_eq!(
+        types,
+        [
+            TokenType::Print,
+            TokenType::Number(1.0),
+            TokenType::Plus,
+            TokenType::Ident,
+            TokenType::Semicolon,
+            TokenType::Eof
+        ]
+    );
+}